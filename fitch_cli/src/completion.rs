@@ -0,0 +1,116 @@
+use std::sync::{Arc, Mutex};
+
+use fitch_core::{ArgKind, Notation, Param, Proof, Prop, PropVariant, StepIndex, RULES};
+use reedline::{Completer, Span, Suggestion};
+
+use crate::COMMANDS;
+
+/// Completes command keywords, rule names and the steps that can be cited by a rule
+pub struct FitchCompleter {
+    /// The proof being written, shared with the main loop
+    proof: Arc<Mutex<Proof>>,
+    /// How the formulas proven by the steps are shown
+    notation: Notation,
+}
+
+impl FitchCompleter {
+    pub fn new(proof: Arc<Mutex<Proof>>, notation: Notation) -> Self {
+        Self { proof, notation }
+    }
+
+    /// The steps that can be cited for a parameter of a rule, e.g. only implications for
+    /// the first argument of `->e`. Without a parameter, the steps that can be copied.
+    fn steps(&self, param: Option<&Param>) -> Vec<(String, String)> {
+        let proof = self.proof.lock().unwrap();
+        proof
+            .accessible_steps()
+            .into_iter()
+            .filter(|(_, step)| {
+                let variant = PropVariant::from(step.prop());
+                match param {
+                    Some(param) => param.accepts(variant),
+                    None => variant != PropVariant::ProofBox,
+                }
+            })
+            .map(|(index, step)| citation(index, step.prop(), self.notation))
+            .collect()
+    }
+}
+
+/// How a step is cited and what it proves
+fn citation(index: StepIndex, prop: &Prop, notation: Notation) -> (String, String) {
+    match prop {
+        Prop::ProofBox(subproof) => {
+            let range = subproof.range();
+            let ellipsis = match notation {
+                Notation::Unicode => "…",
+                Notation::Ascii => "...",
+            };
+            (
+                format!("{}-{}", range.start, range.end),
+                format!(
+                    "[{}] {ellipsis} {}",
+                    subproof.assumption().display(notation),
+                    subproof.derived_prop().display(notation)
+                ),
+            )
+        }
+        prop => (index.to_string(), prop.display(notation).to_string()),
+    }
+}
+
+impl Completer for FitchCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+        let line = &line[..pos];
+        let start = line
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let word = &line[start..];
+        let words = line[..start].split_whitespace().collect::<Vec<_>>();
+
+        let options: Vec<(String, String)> = match words.as_slice() {
+            [] => COMMANDS
+                .iter()
+                .map(|(usage, description)| {
+                    let keyword = usage.split_whitespace().next().unwrap();
+                    (keyword.to_string(), description.to_string())
+                })
+                .collect(),
+            ["rule"] | ["help"] => RULES
+                .iter()
+                // Rules that may not be used in the proof are only worth asking for help about
+                .filter(|info| {
+                    words[0] == "help"
+                        || self.proof.lock().unwrap().allowed_rules().allows(info.name)
+                })
+                .flat_map(|info| {
+                    let label = info.label_in(self.notation);
+                    info.aliases_in(self.notation)
+                        .map(move |alias| (alias.to_string(), label.to_string()))
+                })
+                .collect(),
+            ["copy"] => self.steps(None),
+            ["rule", rule, args @ ..] => RULES
+                .iter()
+                .find(|info| info.aliases.contains(rule))
+                .and_then(|info| info.params.get(args.len()))
+                .filter(|param| param.kind != ArgKind::Prop)
+                .map_or(vec![], |param| self.steps(Some(param))),
+            _ => vec![],
+        };
+
+        options
+            .into_iter()
+            .filter(|(value, _)| value.starts_with(word))
+            .map(|(value, description)| Suggestion {
+                value,
+                description: Some(description),
+                extra: None,
+                span: Span { start, end: pos },
+                append_whitespace: true,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,157 @@
+use std::{env, fs, io, path::PathBuf, process};
+
+use fitch_core::Notation;
+
+const USAGE: &str = "\
+Usage: fitch [options]
+       fitch [options] exercise <problem set>
+       fitch [--json] grade <problem set> <submissions>
+
+  --ascii           Only use ASCII characters when printing proofs and formulas
+  --unicode         Use logical symbols and box-drawing characters (the default)
+  --no-color        Print proofs without colors, which is also the case if NO_COLOR is set
+  --expand          Print formulas in full rather than by the abbreviations defined for them
+  --student <name>  Save the solved exercises as this student, your user name by default
+  --json            Print the report of grade as JSON rather than CSV
+
+Every directory in <submissions> is graded as the solutions of a student, with one file of
+commands per exercise named after its number, e.g. 3.fitch";
+
+/// Settings read from `$XDG_CONFIG_HOME/fitch/config` (or `~/.config/fitch/config`),
+/// which may be overridden by command line flags. The config file contains lines such as
+/// ```notrust
+/// # Print proofs and formulas using only ASCII characters
+/// notation = ascii
+/// # Do not color the printed proofs
+/// colors = false
+/// # Print formulas in full, even where an abbreviation has been defined for them
+/// abbreviations = expanded
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub notation: Notation,
+    pub colors: bool,
+    /// Whether proofs are printed with the abbreviations that have been defined folded
+    pub fold: bool,
+    pub mode: Mode,
+    pub student: String,
+    pub json: bool,
+}
+
+/// What fitch was started to do
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    /// Write proofs from scratch
+    #[default]
+    Prove,
+    /// Work through the exercises of a problem set
+    Exercise(PathBuf),
+    /// Check the solutions that students have submitted for a problem set
+    Grade {
+        problems: PathBuf,
+        submissions: PathBuf,
+    },
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            notation: Notation::default(),
+            colors: env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+            fold: true,
+            mode: Mode::Prove,
+            student: env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .unwrap_or_else(|_| "student".to_string()),
+            json: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut config = Config::default();
+        if let Some(path) = config_path() {
+            match fs::read_to_string(&path) {
+                Ok(content) => config.read(&content),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                Err(error) => eprintln!("Could not read {}: {error}", path.display()),
+            }
+        }
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--ascii" => config.notation = Notation::Ascii,
+                "--unicode" => config.notation = Notation::Unicode,
+                "--no-color" => config.colors = false,
+                "--expand" => config.fold = false,
+                "--student" => {
+                    config.student = expect_value(args.next(), "--student <name>");
+                    if config.student.is_empty() {
+                        eprintln!("The name of the student can't be empty\n\n{USAGE}");
+                        process::exit(2);
+                    }
+                }
+                "--json" => config.json = true,
+                "exercise" => {
+                    let path = expect_value(args.next(), "exercise <problem set>");
+                    config.mode = Mode::Exercise(PathBuf::from(path));
+                }
+                "grade" => {
+                    let usage = "grade <problem set> <submissions>";
+                    config.mode = Mode::Grade {
+                        problems: PathBuf::from(expect_value(args.next(), usage)),
+                        submissions: PathBuf::from(expect_value(args.next(), usage)),
+                    };
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    process::exit(0);
+                }
+                arg => {
+                    eprintln!("Unknown option '{arg}'\n\n{USAGE}");
+                    process::exit(2);
+                }
+            }
+        }
+        config
+    }
+
+    fn read(&mut self, content: &str) {
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+            {
+                Some(("notation", "ascii")) => self.notation = Notation::Ascii,
+                Some(("notation", "unicode")) => self.notation = Notation::Unicode,
+                Some(("colors", "true")) => self.colors = true,
+                Some(("colors", "false")) => self.colors = false,
+                Some(("abbreviations", "folded")) => self.fold = true,
+                Some(("abbreviations", "expanded")) => self.fold = false,
+                _ => eprintln!("Ignoring unknown setting '{line}' in the config file"),
+            }
+        }
+    }
+}
+
+fn expect_value(value: Option<String>, usage: &str) -> String {
+    value.unwrap_or_else(|| {
+        eprintln!("Expected '{usage}'\n\n{USAGE}");
+        process::exit(2);
+    })
+}
+
+fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("fitch").join("config"))
+}
@@ -0,0 +1,230 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use colored::*;
+use fitch_core::{
+    AllowedRules, BoxRange, Error, Exercise, Notation, Proof, RuleName, Skeleton, StepIndex,
+    StepType, Unsolved,
+};
+
+use crate::{config::Config, print_proof, renderer, session::data_dir};
+
+/// Presents the exercises of a problem set one at a time. A solution is saved for every
+/// exercise that is solved, as the commands that build the proof, in
+/// `<data dir>/progress/<student>/<problem set>/<exercise number>.fitch`. Exercises with a
+/// saved solution count as solved and are not presented again. For exercises with a skeleton
+/// proof, the solution is the `justify` commands instead.
+pub struct Exercises {
+    exercises: Vec<Exercise>,
+    /// Where the solutions are saved, if there is a data directory
+    dir: Option<PathBuf>,
+    /// The exercise being worked on, or none once every exercise is solved
+    current: Option<usize>,
+    /// The commands that built the proof of the current exercise
+    commands: Vec<String>,
+    /// The skeleton proof being justified, if the current exercise gives one
+    skeleton: Option<Skeleton>,
+}
+
+/// The name of a student as a single directory name. Every byte other than an ASCII letter,
+/// digit, `_` or `-` is written as `%` and its hex value, so names like `..` or `a/b` stay
+/// inside the progress directory and different names never share a directory.
+fn escape(student: &str) -> String {
+    student
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => (byte as char).to_string(),
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+impl Exercises {
+    pub fn new(exercises: Vec<Exercise>, problem_set: &Path, student: &str) -> Self {
+        let name = problem_set
+            .file_stem()
+            .map_or("exercises".into(), |stem| stem.to_string_lossy());
+        let dir = data_dir().map(|dir| {
+            dir.join("progress")
+                .join(escape(student))
+                .join(name.as_ref())
+        });
+        Self {
+            exercises,
+            dir,
+            current: None,
+            commands: vec![],
+            skeleton: None,
+        }
+    }
+
+    fn solution_path(&self, i: usize) -> Option<PathBuf> {
+        Some(self.dir.as_ref()?.join(format!("{}.fitch", i + 1)))
+    }
+
+    fn is_solved(&self, i: usize) -> bool {
+        self.solution_path(i).is_some_and(|path| path.exists())
+    }
+
+    fn save_solution(&self, i: usize) -> io::Result<()> {
+        let (Some(dir), Some(path)) = (&self.dir, self.solution_path(i)) else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        fs::write(path, self.commands.join("\n") + "\n")
+    }
+
+    /// Start the next exercise that has not been solved, going back to the first one after
+    /// the last. The current exercise is started over if it is the only one left.
+    pub fn next(&mut self, proof: &mut Proof, config: &Config) {
+        let len = self.exercises.len();
+        let start = self.current.map_or(0, |i| i + 1);
+        self.current = (0..len)
+            .map(|i| (start + i) % len)
+            .find(|i| !self.is_solved(*i));
+
+        let Some(i) = self.current else {
+            println!(
+                "{}",
+                "You have solved every exercise in this problem set!".bold()
+            );
+            return;
+        };
+        let exercise = &self.exercises[i];
+        *proof = exercise.start();
+        self.skeleton = exercise.skeleton.clone();
+        self.commands = match self.skeleton {
+            Some(_) => vec![],
+            None => exercise
+                .sequent
+                .premises
+                .iter()
+                .map(|premise| format!("premise {}", premise.display(Notation::Ascii)))
+                .collect(),
+        };
+
+        println!(
+            "{} {}",
+            format!("Exercise {} of {len}:", i + 1).bold(),
+            exercise.sequent.display(config.notation)
+        );
+        let names = |rules: &[RuleName]| {
+            rules
+                .iter()
+                .map(|name| name.info().label_in(config.notation))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &exercise.rules {
+            AllowedRules::All => (),
+            AllowedRules::Only(rules) => {
+                println!("Only these rules may be used: {}", names(rules))
+            }
+            AllowedRules::AllExcept(rules) => {
+                println!("These rules may not be used: {}", names(rules))
+            }
+        }
+        if let Some(max) = exercise.max_steps {
+            println!("The proof may have at most {max} steps.");
+        }
+        println!();
+        match &self.skeleton {
+            Some(skeleton) => {
+                println!("Justify every step of this proof, e.g. 'justify 2 rule ->e 1 3'.");
+                print_skeleton(skeleton, config);
+            }
+            None => print_proof(proof, config),
+        }
+    }
+
+    fn solved(&mut self, i: usize, proof: &mut Proof, config: &Config) {
+        if let Err(error) = self.save_solution(i) {
+            eprintln!("Could not save your solution: {error}");
+        }
+        println!("{}\n", "Well done, the exercise is solved!".green().bold());
+        self.next(proof, config);
+    }
+
+    /// Keep track of a command that changed the proof, and move on to the next exercise
+    /// if the proof now solves the current one
+    pub fn record(&mut self, line: &str, proof: &mut Proof, config: &Config) {
+        let Some(i) = self.current else {
+            return;
+        };
+        if let Some(skeleton) = &self.skeleton {
+            println!("This exercise is solved by justifying the steps of its proof.");
+            print_skeleton(skeleton, config);
+            return;
+        }
+        self.commands.push(line.trim().to_string());
+
+        let exercise = &self.exercises[i];
+        // An edited step may have left the steps after it without a valid justification
+        match exercise.check(proof) {
+            Ok(()) if proof.validate().is_ok() => self.solved(i, proof, config),
+            Err(error @ Unsolved::TooManySteps { .. }) => {
+                println!("{}", error.message(config.notation).yellow());
+            }
+            _ => println!(
+                "{} {}",
+                "Goal:".bold(),
+                exercise.sequent.display(config.notation)
+            ),
+        }
+    }
+
+    /// Justify a step of the skeleton proof, and move on to the next exercise once every
+    /// step is justified
+    pub fn justify(
+        &mut self,
+        line: &str,
+        index: StepIndex,
+        step_type: StepType,
+        ranges: &[BoxRange],
+        proof: &mut Proof,
+        config: &Config,
+    ) -> Result<(), Error> {
+        let (Some(i), Some(skeleton)) = (self.current, self.skeleton.as_mut()) else {
+            println!("There is no skeleton proof to justify in this exercise.");
+            return Ok(());
+        };
+        for range in ranges {
+            skeleton.check_box_range(range)?;
+        }
+        if let Err(error) = self.exercises[i].check_step(index, &step_type) {
+            println!("{}", error.message(config.notation).red());
+            return Ok(());
+        }
+        skeleton.justify(index, step_type)?;
+        self.commands.push(line.trim().to_string());
+
+        print_skeleton(skeleton, config);
+        if skeleton.unjustified().is_empty() {
+            self.solved(i, proof, config);
+        }
+        Ok(())
+    }
+}
+
+fn print_skeleton(skeleton: &Skeleton, config: &Config) {
+    let mut rendered = String::new();
+    match renderer(config).render_skeleton(skeleton, &mut rendered) {
+        Ok(()) => print!("{rendered}"),
+        Err(error) => eprintln!("Could not print the proof: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn student_names_are_escaped() {
+        assert_eq!(escape("ada_lovelace-1"), "ada_lovelace-1");
+        assert_eq!(escape(".."), "%2E%2E");
+        assert_eq!(escape("a/b\\c"), "a%2Fb%5Cc");
+        assert_eq!(escape("zoë"), "zo%C3%AB");
+    }
+}
@@ -0,0 +1,500 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use fitch_core::{Definitions, Error, Exercise, Notation, Proof, Skeleton, Unsolved};
+use fitch_syntax::{parse_command_with, Command};
+
+use crate::{config::Config, read_problem_set, run_silently};
+
+/// How a submitted solution to an exercise was graded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Valid,
+    /// A line could not be parsed, or its step could not be justified
+    Invalid,
+    /// The proof is empty, leaves a proof box open or leaves steps of a skeleton proof
+    /// unjustified
+    Incomplete,
+    /// The proof does not end with the conclusion of the sequent, e.g. because it stops
+    /// before the conclusion is reached
+    WrongConclusion,
+    DisallowedRule,
+    ExtraPremise,
+    TooManySteps,
+    /// No solution was submitted
+    Missing,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Valid => "valid",
+            Status::Invalid => "invalid",
+            Status::Incomplete => "incomplete",
+            Status::WrongConclusion => "wrong_conclusion",
+            Status::DisallowedRule => "disallowed_rule",
+            Status::ExtraPremise => "extra_premise",
+            Status::TooManySteps => "too_many_steps",
+            Status::Missing => "missing",
+        }
+    }
+}
+
+/// The grade of one exercise for one student
+struct Grade {
+    student: String,
+    /// Numbered from one, like the solution files
+    exercise: usize,
+    sequent: String,
+    status: Status,
+    /// The line of the solution where grading stopped, if a line was at fault
+    line: Option<usize>,
+    message: String,
+}
+
+/// Check every solution in the submissions directory against the problem set, and print
+/// a report with one row per student and exercise
+pub fn grade(problems: &Path, submissions: &Path, config: &Config) {
+    let exercises = read_problem_set(problems);
+    let students = student_dirs(submissions).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {error}", submissions.display());
+        process::exit(1);
+    });
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let grades = grade_students(&students, &exercises, config.notation, threads);
+    let report = if config.json {
+        to_json(&grades)
+    } else {
+        to_csv(&grades)
+    };
+    if let Err(error) = io::stdout().write_all(report.as_bytes()) {
+        eprintln!("Could not print the report: {error}");
+    }
+}
+
+/// Grade the students on up to `threads` threads, ordered by student and exercise
+fn grade_students(
+    students: &[(String, PathBuf)],
+    exercises: &[Exercise],
+    notation: Notation,
+    threads: usize,
+) -> Vec<Grade> {
+    let next = AtomicUsize::new(0);
+    let grades = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..threads.min(students.len()).max(1) {
+            scope.spawn(|| {
+                while let Some((student, dir)) = students.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    let graded = grade_student(student, dir, exercises, notation);
+                    grades.lock().unwrap().extend(graded);
+                }
+            });
+        }
+    });
+
+    let mut grades = grades.into_inner().unwrap();
+    grades.sort_by(|a, b| (&a.student, a.exercise).cmp(&(&b.student, b.exercise)));
+    grades
+}
+
+/// Every directory of submissions, as the name of the student and the path, ordered by name
+fn student_dirs(submissions: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut students = vec![];
+    for entry in fs::read_dir(submissions)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            students.push((
+                entry.file_name().to_string_lossy().into_owned(),
+                entry.path(),
+            ));
+        }
+    }
+    students.sort();
+    Ok(students)
+}
+
+fn grade_student(
+    student: &str,
+    dir: &Path,
+    exercises: &[Exercise],
+    notation: Notation,
+) -> Vec<Grade> {
+    exercises
+        .iter()
+        .enumerate()
+        .map(|(i, exercise)| {
+            let path = dir.join(format!("{}.fitch", i + 1));
+            let (status, line, message) = match fs::read_to_string(&path) {
+                Ok(solution) => check_solution(&solution, exercise, notation),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    (Status::Missing, None, String::new())
+                }
+                Err(error) => (
+                    Status::Invalid,
+                    None,
+                    format!("Could not read {}: {error}", path.display()),
+                ),
+            };
+            Grade {
+                student: student.to_string(),
+                exercise: i + 1,
+                sequent: exercise.sequent.display(notation).to_string(),
+                status,
+                line,
+                message,
+            }
+        })
+        .collect()
+}
+
+/// The commands of a solution with their line numbers, counted from one. Empty lines and
+/// lines starting with `#` are skipped.
+fn commands(solution: &str) -> impl Iterator<Item = (usize, &str)> + '_ {
+    solution
+        .lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Parse a command of a solution, expanding the abbreviations that have been defined so far
+fn parse(line: &str, definitions: &Definitions) -> Result<Command, String> {
+    parse_command_with(line, definitions).map_err(|_| format!("Could not parse '{line}'"))
+}
+
+fn unsolved_status(unsolved: &Unsolved) -> Status {
+    match unsolved {
+        Unsolved::OpenProofBox | Unsolved::WrongConclusion { got: None, .. } => Status::Incomplete,
+        Unsolved::WrongConclusion { .. } => Status::WrongConclusion,
+        Unsolved::DisallowedRule { .. } => Status::DisallowedRule,
+        Unsolved::ExtraPremise { .. } => Status::ExtraPremise,
+        Unsolved::TooManySteps { .. } => Status::TooManySteps,
+    }
+}
+
+fn error_status(error: &Error) -> Status {
+    match error {
+        Error::RuleNotAllowed { .. } => Status::DisallowedRule,
+        Error::NotAPremise { .. } => Status::ExtraPremise,
+        _ => Status::Invalid,
+    }
+}
+
+/// Run the commands of a solution one by one, stopping at the first line that fails
+fn check_solution(
+    solution: &str,
+    exercise: &Exercise,
+    notation: Notation,
+) -> (Status, Option<usize>, String) {
+    if let Some(skeleton) = &exercise.skeleton {
+        return check_justifications(solution, exercise, skeleton.clone(), notation);
+    }
+
+    let mut proof = Proof::with_rules(exercise.rules.clone());
+    for (n, line) in commands(solution) {
+        let command = match parse(line, proof.definitions()) {
+            Ok(command) => command,
+            Err(message) => return (Status::Invalid, Some(n), message),
+        };
+        if let Err(error) = run_silently(command, &mut proof) {
+            return (error_status(&error), Some(n), error.message(notation));
+        }
+    }
+
+    // Steps after an edited step are kept even if they are no longer valid
+    if let Err(violations) = proof.validate() {
+        return (Status::Invalid, None, violations[0].message(notation));
+    }
+    match exercise.check(&proof) {
+        Ok(()) => (Status::Valid, None, String::new()),
+        Err(error) => (unsolved_status(&error), None, error.message(notation)),
+    }
+}
+
+/// Justify the steps of the skeleton proof of the exercise by the `justify` commands of the
+/// solution, which must leave no step unjustified
+fn check_justifications(
+    solution: &str,
+    exercise: &Exercise,
+    mut skeleton: Skeleton,
+    notation: Notation,
+) -> (Status, Option<usize>, String) {
+    for (n, line) in commands(solution) {
+        let (index, step_type, ranges) = match parse(line, &Definitions::default()) {
+            Ok(Command::Justify(index, step_type, ranges)) => (index, step_type, ranges),
+            Ok(_) => {
+                return (
+                    Status::Invalid,
+                    Some(n),
+                    "Only justify commands may be used in this exercise.".to_string(),
+                )
+            }
+            Err(message) => return (Status::Invalid, Some(n), message),
+        };
+        if let Err(error) = exercise.check_step(index, &step_type) {
+            return (unsolved_status(&error), Some(n), error.message(notation));
+        }
+        let justified = ranges
+            .iter()
+            .try_for_each(|range| skeleton.check_box_range(range))
+            .and_then(|()| skeleton.justify(index, step_type));
+        if let Err(error) = justified {
+            return (error_status(&error), Some(n), error.message(notation));
+        }
+    }
+
+    match skeleton.unjustified().as_slice() {
+        [] => (Status::Valid, None, String::new()),
+        [step] => (
+            Status::Incomplete,
+            None,
+            format!("Step {step} is not justified."),
+        ),
+        steps => {
+            let steps = steps
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>();
+            (
+                Status::Incomplete,
+                None,
+                format!("Steps {} are not justified.", steps.join(", ")),
+            )
+        }
+    }
+}
+
+fn to_csv(grades: &[Grade]) -> String {
+    let mut csv = String::from("student,exercise,sequent,status,line,message\n");
+    for grade in grades {
+        let fields = [
+            grade.student.clone(),
+            grade.exercise.to_string(),
+            grade.sequent.clone(),
+            grade.status.name().to_string(),
+            grade.line.map_or(String::new(), |line| line.to_string()),
+            grade.message.clone(),
+        ];
+        let fields = fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn to_json(grades: &[Grade]) -> String {
+    let rows = grades
+        .iter()
+        .map(|grade| {
+            format!(
+                "  {{\"student\": {}, \"exercise\": {}, \"sequent\": {}, \"status\": {}, \"line\": {}, \"message\": {}}}",
+                json_string(&grade.student),
+                grade.exercise,
+                json_string(&grade.sequent),
+                json_string(grade.status.name()),
+                grade.line.map_or("null".to_string(), |line| line.to_string()),
+                json_string(&grade.message),
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("[\n{}\n]\n", rows.join(",\n"))
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fitch_syntax::parse_problem_set;
+
+    fn check(problem: &str, solution: &str) -> (Status, Option<usize>) {
+        let exercises =
+            parse_problem_set(problem).unwrap_or_else(|_| panic!("could not parse {problem}"));
+        let (status, line, _) = check_solution(solution, &exercises[0], Notation::Unicode);
+        (status, line)
+    }
+
+    #[test]
+    fn statuses() {
+        let problem = "p, q |- q & p";
+        let solved = "premise p\npremise q\nrule &i 2 1";
+        assert_eq!(check(problem, solved), (Status::Valid, None));
+        assert_eq!(
+            check(problem, "premise p\n# a comment\nfoo"),
+            (Status::Invalid, Some(3))
+        );
+        assert_eq!(
+            check(problem, "premise p\ncopy 5"),
+            (Status::Invalid, Some(2))
+        );
+        assert_eq!(check(problem, ""), (Status::Incomplete, None));
+        assert_eq!(
+            check(problem, "premise p\nassume q"),
+            (Status::Incomplete, None)
+        );
+        assert_eq!(
+            check(problem, "premise p\npremise q\ncopy 1"),
+            (Status::WrongConclusion, None)
+        );
+        assert_eq!(
+            check("p, q |- q & p\n    without = &i", solved),
+            (Status::DisallowedRule, Some(3))
+        );
+        assert_eq!(
+            check(problem, "premise p\npremise q\npremise r\nrule &i 2 1"),
+            (Status::ExtraPremise, None)
+        );
+        assert_eq!(
+            check("p, q |- q & p\n    steps = 2", solved),
+            (Status::TooManySteps, None)
+        );
+
+        let exercises = parse_problem_set(problem).unwrap_or_else(|_| panic!("{problem}"));
+        let grades = grade_student(
+            "student",
+            Path::new("/nonexistent"),
+            &exercises,
+            Notation::Ascii,
+        );
+        assert_eq!(grades[0].status, Status::Missing);
+    }
+
+    #[test]
+    fn skeleton_statuses() {
+        let problem = "p |- p & p\n    skeleton = p; p & p";
+        assert_eq!(
+            check(problem, "justify 1 premise\njustify 2 rule &i 1 1"),
+            (Status::Valid, None)
+        );
+        assert_eq!(
+            check(problem, "justify 1 premise"),
+            (Status::Incomplete, None)
+        );
+        // The conclusion is not a premise of the exercise
+        assert_eq!(
+            check(problem, "justify 1 premise\njustify 2 premise"),
+            (Status::ExtraPremise, Some(2))
+        );
+        assert_eq!(check(problem, "premise p"), (Status::Invalid, Some(1)));
+
+        let problem = "p |- p & p\n    rules = ->i\n    skeleton = p; p & p";
+        assert_eq!(
+            check(problem, "justify 1 premise\njustify 2 rule &i 1 1"),
+            (Status::DisallowedRule, Some(2))
+        );
+    }
+
+    /// Grades the same solutions of many students on one thread and on every core. Run it
+    /// with `cargo test --release grading_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn grading_benchmark() {
+        let problems = "\
+p -> q, q -> r |- p -> r
+p | q |- q | p
+--p -> q, -q |- -p
+";
+        let solutions = [
+            "premise p -> q\npremise q -> r\nassume p\nrule ->e 1 3\nrule ->e 2 4\ndischarge\nrule ->i 3-5",
+            "premise p | q\nassume p\nrule |i_rhs q 2\ndischarge\nassume q\nrule |i_lhs 4 p\n\
+             discharge\nrule |e 1 2-3 4-5",
+            "premise --p -> q\npremise -q\nrule mt 1 2\nrule --e 3\nassume p\nrule --i 5\n\
+             rule ->e 1 6\nrule -e 7 2\ndischarge\nrule -i 5-8",
+        ];
+        let exercises = parse_problem_set(problems).unwrap();
+        let dir = std::env::temp_dir().join(format!("fitch-grading-{}", process::id()));
+        let students = (0..500)
+            .map(|n| {
+                let student = format!("student{n}");
+                let path = dir.join(&student);
+                fs::create_dir_all(&path).unwrap();
+                for (i, solution) in solutions.iter().enumerate() {
+                    fs::write(path.join(format!("{}.fitch", i + 1)), solution).unwrap();
+                }
+                (student, path)
+            })
+            .collect::<Vec<_>>();
+
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        for threads in [1, cores] {
+            let start = std::time::Instant::now();
+            let grades = grade_students(&students, &exercises, Notation::Unicode, threads);
+            println!(
+                "Graded {} solutions on {threads} threads in {:?}",
+                grades.len(),
+                start.elapsed()
+            );
+            for grade in grades {
+                assert_eq!(grade.status, Status::Valid, "{}", grade.message);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fields_are_quoted() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("p, q"), "\"p, q\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(
+            json_string("say \"hi\"\\\nnext\u{1}"),
+            "\"say \\\"hi\\\"\\\\\\nnext\\u0001\""
+        );
+
+        let grades = [Grade {
+            student: "a, b".to_string(),
+            exercise: 1,
+            sequent: "p |- p".to_string(),
+            status: Status::Invalid,
+            line: Some(2),
+            message: "Could not parse 'x \"y\"'".to_string(),
+        }];
+        assert_eq!(
+            to_csv(&grades).lines().nth(1),
+            Some("\"a, b\",1,p |- p,invalid,2,\"Could not parse 'x \"\"y\"\"'\"")
+        );
+        assert!(to_json(&grades).contains(
+            "{\"student\": \"a, b\", \"exercise\": 1, \"sequent\": \"p |- p\", \"status\": \"invalid\", \"line\": 2, \"message\": \"Could not parse 'x \\\"y\\\"'\"}"
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::{Arc, Mutex};
+
+use fitch_core::{Notation, Proof, TextStyle, Theme};
+use fitch_syntax::{highlight, parse_command_with, Command, Highlight};
+use nu_ansi_term::{Color, Style};
+use reedline::{Hinter, History, StyledText};
+
+/// Colors the parts of a command as it is typed and underlines anything that is out of place
+pub struct FitchHighlighter;
+
+impl reedline::Highlighter for FitchHighlighter {
+    fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut styles = vec![Style::new(); chars.len()];
+        for (span, highlight) in highlight(line) {
+            styles[span].fill(style(highlight));
+        }
+
+        let mut text = StyledText::new();
+        for (c, style) in chars.into_iter().zip(styles) {
+            match text.buffer.last_mut() {
+                Some((last, s)) if *last == style => s.push(c),
+                _ => text.push((style, c.to_string())),
+            }
+        }
+        text
+    }
+}
+
+/// The colors used when printing proofs: the most recent step stands out, together with the
+/// steps that it was justified by
+pub fn proof_theme() -> Theme {
+    let text_style =
+        |style: Style| TextStyle::new(style.prefix().to_string(), style.suffix().to_string());
+    Theme {
+        index: text_style(Color::Yellow.normal()),
+        justification: text_style(Color::DarkGray.normal()),
+        border: text_style(Color::Blue.normal()),
+        latest: text_style(Color::Green.bold()),
+        cited: text_style(Color::Cyan.normal()),
+        note: text_style(Style::new().dimmed()),
+    }
+}
+
+fn style(highlight: Highlight) -> Style {
+    match highlight {
+        Highlight::Keyword => Color::Blue.bold(),
+        Highlight::RuleName => Color::Magenta.normal(),
+        Highlight::Index => Color::Yellow.normal(),
+        Highlight::Symbol => Style::new(),
+        Highlight::Bottom => Color::Red.normal(),
+        Highlight::Operator => Color::Cyan.normal(),
+        Highlight::Text => Style::new().italic(),
+        Highlight::Invalid => Color::Red.underline(),
+    }
+}
+
+/// Shows what the rule being typed would derive, or why it can't be applied
+pub struct RuleHinter {
+    /// The proof being written, shared with the main loop
+    proof: Arc<Mutex<Proof>>,
+    notation: Notation,
+}
+
+impl RuleHinter {
+    pub fn new(proof: Arc<Mutex<Proof>>, notation: Notation) -> Self {
+        Self { proof, notation }
+    }
+}
+
+impl Hinter for RuleHinter {
+    fn handle(
+        &mut self,
+        line: &str,
+        pos: usize,
+        _history: &dyn History,
+        use_ansi_coloring: bool,
+    ) -> String {
+        if pos != line.len() {
+            return String::new();
+        }
+        let proof = self.proof.lock().unwrap();
+        let Ok(Command::Rule(rule, ranges)) = parse_command_with(line, proof.definitions()) else {
+            return String::new();
+        };
+
+        let hint = ranges
+            .iter()
+            .try_for_each(|range| proof.check_box_range(range))
+            .and_then(|()| proof.derive(&rule));
+        let (hint, color) = match hint {
+            Ok(prop) => {
+                let turnstile = match self.notation {
+                    Notation::Unicode => "⊢",
+                    Notation::Ascii => "|-",
+                };
+                let prop = prop.display(self.notation);
+                (format!("  {turnstile} {prop}"), Color::DarkGray)
+            }
+            Err(error) => (format!("  {}", error.message(self.notation)), Color::Red),
+        };
+
+        if use_ansi_coloring {
+            color.dimmed().paint(hint).to_string()
+        } else {
+            hint
+        }
+    }
+
+    /// The hint is not something that should be inserted into the command
+    fn complete_hint(&self) -> String {
+        String::new()
+    }
+
+    fn next_hint_token(&self) -> String {
+        String::new()
+    }
+}
@@ -1,8 +1,35 @@
+mod completion;
+mod config;
+mod exercise;
+mod grade;
+mod highlight;
+mod session;
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    process,
+    sync::{Arc, Mutex},
+};
+
 use colored::*;
-use fitch_core::{latex, print_proof, Error, Proof};
-use fitch_syntax::{parse_command, Command, Source};
+use completion::FitchCompleter;
+use config::{Config, Mode};
+use exercise::Exercises;
+use fitch_core::{
+    latex, latex_folded, Error, Exercise, Notation, Proof, Renderer, RuleName, StepIndex, RULES,
+};
+use fitch_syntax::{
+    parse_command, parse_command_with, parse_problem_set, report_error, Command, Report, Source,
+};
+use highlight::{proof_theme, FitchHighlighter, RuleHinter};
 use rand::seq::SliceRandom;
-use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
+use reedline::{
+    default_emacs_keybindings, ColumnarMenu, DefaultPrompt, DefaultPromptSegment, Emacs,
+    FileBackedHistory, KeyCode, KeyModifiers, Reedline, ReedlineEvent, ReedlineMenu, Signal,
+};
+use session::{changes_proof, data_dir, Journal};
 
 fn greet() {
     println!(
@@ -27,6 +54,265 @@ quit"
     );
 }
 
+const COMMANDS: &[(&str, &str)] = &[
+    ("premise <formula>", "Add a new premise"),
+    ("copy <step>", "Copy a previously proven formula"),
+    ("assume <formula>", "Open a proof box with an assumption"),
+    ("discharge", "Close the current proof box"),
+    (
+        "rule <rule> <arguments...>",
+        "Apply a rule, see the list below",
+    ),
+    (
+        "derive <formula>",
+        "Derive a formula by whichever rule fits",
+    ),
+    (
+        "insert before <step> <commands...>",
+        "Insert steps earlier in the proof, separated by ;",
+    ),
+    (
+        "edit <step> <command>",
+        "Replace a step, and check the steps after it again",
+    ),
+    (
+        "define <name> := <formula>",
+        "Define an abbreviation that can be used in later formulas",
+    ),
+    (
+        "note [step] \"<text>\"",
+        "Add a note to a step or to the proof, \"\" removes it",
+    ),
+    ("undo", "Undo the latest step or definition"),
+    ("latex", "Generate LaTeX code to typeset the proof"),
+    (
+        "stats",
+        "Count the steps, proof boxes and rules of the proof",
+    ),
+    ("skip", "Move on to the next exercise"),
+    (
+        "justify <step> <justification>",
+        "Justify a step of a skeleton proof",
+    ),
+    ("help [rule]", "Print this message, or explain a rule"),
+    ("quit", "Quit the program"),
+];
+
+fn print_help() {
+    println!("{}", "Commands:".bold());
+    for (usage, description) in COMMANDS {
+        println!("  {usage:<40} {}", description.bright_black());
+    }
+
+    println!("\n{}", "Rules:".bold());
+    for info in RULES {
+        println!(
+            "  {:<40} {}",
+            format!("rule {}", info.usage()),
+            info.description.bright_black()
+        );
+    }
+    println!(
+        "\nType {} to see how a rule is used, e.g. {}",
+        "help <rule>".italic(),
+        "help ->e".italic()
+    );
+}
+
+fn print_rule_help(name: RuleName, config: &Config) {
+    let info = name.info();
+    match config.notation {
+        Notation::Unicode => println!("{} ({})", info.label.bold(), info.canonical_name()),
+        Notation::Ascii => println!("{}", info.canonical_name().bold()),
+    }
+    println!("{}\n", info.description);
+    println!("{}\n", info.schema);
+
+    println!("{}", "Arguments, in order:".bold());
+    for (i, param) in info.params.iter().enumerate() {
+        println!("  {}. {} ({})", i + 1, param.name, param.kind);
+    }
+    if info.params.is_empty() {
+        println!("  none");
+    }
+    println!(
+        "{} {}\n",
+        "Also written as:".bold(),
+        info.aliases_in(config.notation)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    println!("{}", "Example:".bold());
+    let mut proof = Proof::new();
+    for line in info.example.lines() {
+        println!("  {}", line.italic());
+        // The examples are checked by the tests in fitch_syntax
+        if let Ok(command) = parse_command(line) {
+            let _ = run_silently(command, &mut proof);
+        }
+    }
+    println!();
+    print_proof(&proof, config);
+}
+
+fn print_stats(proof: &Proof, config: &Config) {
+    let stats = proof.stats();
+    println!("{} {}", "Lines:".bold(), stats.lines);
+    println!(
+        "{} {}, nested at most {} deep",
+        "Proof boxes:".bold(),
+        stats.boxes,
+        stats.max_depth
+    );
+    println!("{} {}", "Copies:".bold(), stats.copies);
+    println!("{} {}", "Classical rules:".bold(), stats.classical);
+    println!(
+        "{} at most {}, {:.1} on average",
+        "Formula size:".bold(),
+        stats.max_formula_size,
+        stats.average_formula_size()
+    );
+
+    println!("{}", "Rules:".bold());
+    for info in RULES {
+        if let Some(count) = stats.rules.get(&info.name) {
+            println!("  {:<10} {count}", info.label_in(config.notation));
+        }
+    }
+    if stats.rules.is_empty() {
+        println!("  none");
+    }
+}
+
+/// Offer to restore the proof from a session that did not end with `quit`
+fn restore(previous: &[String], journal: &mut Journal, proof: &mut Proof, config: &Config) {
+    print!(
+        "{} Restore it? [Y/n] ",
+        "It looks like your last proof was never finished.".bold()
+    );
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+
+    if answer.trim().eq_ignore_ascii_case("n") {
+        if let Err(error) = journal.clear() {
+            eprintln!("Could not clear the saved session: {error}");
+        }
+        return;
+    }
+
+    for line in previous {
+        if let Ok(command) = parse_command_with(line, proof.definitions()) {
+            let _ = run_silently(command, proof);
+        }
+    }
+    print_proof(proof, config);
+}
+
+/// Read a problem set. Exits if the problem set can't be read or has no exercises.
+fn read_problem_set(path: &Path) -> Vec<Exercise> {
+    let content = fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {error}", path.display());
+        process::exit(1);
+    });
+    match parse_problem_set(&content) {
+        Ok(exercises) if exercises.is_empty() => {
+            eprintln!("There are no exercises in {}", path.display());
+            process::exit(1);
+        }
+        Ok(exercises) => exercises,
+        Err(errors) => {
+            for report in errors {
+                report.eprint(Source::from(&content)).unwrap();
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// Run a command that only changes the proof, without printing anything
+fn run_silently(command: Command, proof: &mut Proof) -> Result<(), Error> {
+    match command {
+        Command::Rule(rule, ranges) => {
+            for range in &ranges {
+                proof.check_box_range(range)?;
+            }
+            proof.apply_rule(&rule).map(|_| ())
+        }
+        Command::Copy(i) => proof.copy(i).map(|_| ()),
+        Command::Premise(prop) => {
+            proof.add_premise(prop);
+            Ok(())
+        }
+        Command::Assume(prop) => {
+            proof.add_assumption(prop);
+            Ok(())
+        }
+        Command::Derive(prop) => {
+            let rule = proof.find_rule(&prop)?;
+            proof.apply_rule(&rule).map(|_| ())
+        }
+        Command::Insert(index, commands) => proof.insert_before(index, |proof| {
+            commands
+                .into_iter()
+                .try_for_each(|command| run_silently(command, proof))
+        }),
+        // Steps that are no longer valid are kept, and found by validating the proof
+        Command::Edit(index, command) => proof
+            .edit(index, |proof| run_silently(*command, proof))
+            .map(|_| ()),
+        Command::Note(index, text) => set_note(proof, index, text),
+        Command::Define(name, prop) => proof.define(name, prop),
+        Command::Discharge => proof.close_scope(),
+        Command::Undo => {
+            proof.undo();
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Attach a note to a step, or to the whole proof. An empty note removes the note.
+fn set_note(proof: &mut Proof, index: Option<StepIndex>, text: String) -> Result<(), Error> {
+    let note = Some(text.trim().to_string()).filter(|note| !note.is_empty());
+    match index {
+        Some(index) => proof.set_step_note(index, note),
+        None => {
+            proof.set_note(note);
+            Ok(())
+        }
+    }
+}
+
+/// Parse a command, expanding the abbreviations that have been defined in the proof
+fn parse_in_proof<'a>(line: &'a str, proof: &Proof) -> Result<Command, Vec<Report<'a>>> {
+    parse_command_with(line, proof.definitions())
+}
+
+fn renderer(config: &Config) -> Renderer {
+    let renderer = Renderer::new()
+        .notation(config.notation)
+        .width(terminal_width())
+        .fold_definitions(config.fold);
+    if config.colors {
+        renderer.theme(proof_theme())
+    } else {
+        renderer
+    }
+}
+
+fn print_proof(proof: &Proof, config: &Config) {
+    if let Err(error) = renderer(config).write(proof, &mut io::stdout()) {
+        eprintln!("Could not print the proof: {error}");
+    }
+}
+
+/// Proofs are laid out to fit the terminal, falling back to 80 columns if its size is unknown
+fn terminal_width() -> usize {
+    crossterm::terminal::size().map_or(80, |(columns, _)| columns as usize)
+}
+
 fn say_goodbye() {
     let phrase = [
         "Bye!",
@@ -41,29 +327,135 @@ fn say_goodbye() {
 }
 
 fn main() {
+    let config = Config::load();
+    let mut keybindings = default_emacs_keybindings();
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::Tab,
+        ReedlineEvent::UntilFound(vec![
+            ReedlineEvent::Menu("completion_menu".to_string()),
+            ReedlineEvent::MenuNext,
+        ]),
+    );
+    let mut exercises = match &config.mode {
+        Mode::Prove => None,
+        Mode::Exercise(path) => {
+            let exercises = read_problem_set(path);
+            Some(Exercises::new(exercises, path, &config.student))
+        }
+        Mode::Grade {
+            problems,
+            submissions,
+        } => {
+            grade::grade(problems, submissions, &config);
+            return;
+        }
+    };
+    // The completer and hinter look at the proof while a command is typed
+    let shared = Arc::new(Mutex::new(Proof::new()));
     let mut line_editor = Reedline::create();
+    let mut journal = None;
+    if let Some(dir) = data_dir() {
+        match FileBackedHistory::with_file(1000, dir.join("history.txt")) {
+            Ok(history) => line_editor = line_editor.with_history(Box::new(history)),
+            Err(error) => eprintln!("Could not open the command history: {error}"),
+        }
+        // The solutions of exercises are saved separately
+        if exercises.is_none() {
+            match Journal::open(dir) {
+                Ok((mut opened, previous)) => {
+                    if !previous.is_empty() {
+                        restore(&previous, &mut opened, &mut shared.lock().unwrap(), &config);
+                    }
+                    journal = Some(opened);
+                }
+                Err(error) => eprintln!("Your session will not be saved: {error}"),
+            }
+        }
+    }
+
+    let mut line_editor = line_editor
+        .with_completer(Box::new(FitchCompleter::new(
+            Arc::clone(&shared),
+            config.notation,
+        )))
+        .with_highlighter(Box::new(FitchHighlighter))
+        .with_hinter(Box::new(RuleHinter::new(
+            Arc::clone(&shared),
+            config.notation,
+        )))
+        .with_menu(ReedlineMenu::EngineCompleter(Box::new(
+            ColumnarMenu::default().with_name("completion_menu"),
+        )))
+        .with_edit_mode(Box::new(Emacs::new(keybindings)));
 
     let prompt = DefaultPrompt::new(
         DefaultPromptSegment::Basic("fitch".into()),
         DefaultPromptSegment::Empty,
     );
 
-    let mut proof = Proof::new();
-
     greet();
+    if let Some(exercises) = exercises.as_mut() {
+        exercises.next(&mut shared.lock().unwrap(), &config);
+    }
 
     loop {
         let sig = line_editor.read_line(&prompt);
+        // The proof must not be locked while the line is read, as the completer and hinter
+        // lock it
+        let mut proof = shared.lock().unwrap();
+        let proof = &mut *proof;
         match sig {
-            Ok(Signal::Success(line)) => match parse_command(&line) {
-                Ok(command) => match run(command, &mut proof, &mut line_editor) {
-                    Ok(false) => break,
-                    Ok(true) => continue,
-                    Err(error) => {
-                        println!("Error: {}", error);
-                        continue;
+            Ok(Signal::Success(line)) => match parse_in_proof(&line, proof) {
+                Ok(Command::Justify(index, step_type, ranges)) => match exercises.as_mut() {
+                    Some(exercises) => {
+                        if let Err(error) =
+                            exercises.justify(&line, index, step_type, &ranges, proof, &config)
+                        {
+                            report_error(&line, 0, &error, config.notation)
+                                .eprint(Source::from(&line))
+                                .unwrap();
+                        }
                     }
+                    None => println!("There is no skeleton proof to justify."),
                 },
+                Ok(command) => {
+                    let record = changes_proof(&command);
+                    let skip = command == Command::Skip;
+                    match run(command, proof, &mut line_editor, &config) {
+                        Ok(false) => {
+                            // The proof was left on purpose, so there is nothing to restore
+                            if let Some(Err(error)) = journal.as_mut().map(Journal::clear) {
+                                eprintln!("Could not clear the saved session: {error}");
+                            }
+                            break;
+                        }
+                        Ok(true) => {
+                            if let Some(Err(error)) = journal
+                                .as_mut()
+                                .filter(|_| record)
+                                .map(|journal| journal.record(&line))
+                            {
+                                eprintln!("Could not save the last command: {error}");
+                            }
+                            match exercises.as_mut() {
+                                Some(exercises) if record => {
+                                    exercises.record(&line, proof, &config)
+                                }
+                                Some(exercises) if skip => exercises.next(proof, &config),
+                                None if skip => println!("There is no exercise to skip."),
+                                _ => (),
+                            }
+                            continue;
+                        }
+                        Err(Failure { error, command }) => {
+                            report_error(&line, command, &error, config.notation)
+                                .eprint(Source::from(&line))
+                                .unwrap();
+                            continue;
+                        }
+                    }
+                }
                 Err(errors) => errors
                     .into_iter()
                     .for_each(|report| report.eprint(Source::from(&line)).unwrap()),
@@ -77,10 +469,31 @@ fn main() {
     }
 }
 
-fn run(command: Command, proof: &mut Proof, line_editor: &mut Reedline) -> Result<bool, Error> {
+/// An error of a command, together with the position of the inserted command that caused
+/// it (zero for anything other than an insertion)
+struct Failure {
+    error: Error,
+    command: usize,
+}
+
+impl From<Error> for Failure {
+    fn from(error: Error) -> Self {
+        Failure { error, command: 0 }
+    }
+}
+
+fn run(
+    command: Command,
+    proof: &mut Proof,
+    line_editor: &mut Reedline,
+    config: &Config,
+) -> Result<bool, Failure> {
     let (should_continue, clear_screen) = match command.clone() {
         // TODO: remove the result from the tuple
-        Command::Rule(rule) => {
+        Command::Rule(rule, ranges) => {
+            for range in &ranges {
+                proof.check_box_range(range)?;
+            }
             proof.apply_rule(&rule)?;
             (true, true)
         }
@@ -96,10 +509,61 @@ fn run(command: Command, proof: &mut Proof, line_editor: &mut Reedline) -> Resul
             proof.add_assumption(prop);
             (true, true)
         }
+        Command::Derive(prop) => {
+            let rule = proof.find_rule(&prop)?;
+            proof.apply_rule(&rule)?;
+            line_editor.clear_screen().unwrap();
+            print_proof(proof, config);
+            println!(
+                "Derived by {}",
+                rule.display(config.notation).to_string().bold()
+            );
+            (true, false)
+        }
         Command::Discharge => {
             proof.close_scope()?;
             (true, true)
         }
+        Command::Insert(index, commands) => {
+            let mut inserted = 0;
+            proof
+                .insert_before(index, |proof| {
+                    commands.into_iter().try_for_each(|command| {
+                        run_silently(command, proof)?;
+                        inserted += 1;
+                        Ok(())
+                    })
+                })
+                .map_err(|error| Failure {
+                    error,
+                    command: inserted,
+                })?;
+            (true, true)
+        }
+        Command::Edit(index, command) => {
+            let violations = proof.edit(index, |proof| run_silently(*command, proof))?;
+            line_editor.clear_screen().unwrap();
+            print_proof(proof, config);
+            if !violations.is_empty() {
+                println!("{}", "These steps are no longer valid:".red().bold());
+                for violation in violations {
+                    println!("  {}", violation.message(config.notation));
+                }
+            }
+            (true, false)
+        }
+        Command::Note(index, text) => {
+            set_note(proof, index, text)?;
+            (true, true)
+        }
+        Command::Define(name, prop) => {
+            let definition = format!("{name} := {}", prop.display(config.notation));
+            proof.define(name, prop)?;
+            line_editor.clear_screen().unwrap();
+            print_proof(proof, config);
+            println!("Defined {}", definition.bold());
+            (true, false)
+        }
         Command::Undo => {
             proof.undo();
             (true, true)
@@ -108,12 +572,27 @@ fn run(command: Command, proof: &mut Proof, line_editor: &mut Reedline) -> Resul
             say_goodbye();
             (false, false)
         }
-        Command::Help => {
-            println!("TODO: add help. You are own your own for now :^)");
+        Command::Help(None) => {
+            print_help();
+            (true, false)
+        }
+        Command::Help(Some(name)) => {
+            print_rule_help(name, config);
+            (true, false)
+        }
+        // Handled by the exercises
+        Command::Skip | Command::Justify(..) => (true, false),
+        Command::Stats => {
+            print_stats(proof, config);
             (true, false)
         }
         Command::Latex => {
-            if let Some(latex_text) = latex(&proof) {
+            let latex_text = if config.fold {
+                latex_folded(proof)
+            } else {
+                latex(proof)
+            };
+            if let Some(latex_text) = latex_text {
                 println!("{imports}{latex_text}", imports = "Remember to also include these packages:\n\\usepackage{amsmath}\n\\usepackage{logicproof}\n\n".bright_black());
             } else {
                 println!(
@@ -126,7 +605,7 @@ fn run(command: Command, proof: &mut Proof, line_editor: &mut Reedline) -> Resul
 
     if clear_screen {
         line_editor.clear_screen().unwrap();
-        print_proof(proof);
+        print_proof(proof, config);
     }
 
     Ok(should_continue)
@@ -0,0 +1,69 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use fitch_syntax::Command;
+
+/// Where the command history and the session journal are kept, `$XDG_DATA_HOME/fitch`
+/// or `~/.local/share/fitch`
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("fitch"))
+}
+
+/// Every command that changed the proof during the current session, written to disk as soon
+/// as it has been run. Replaying the journal restores the proof if fitch did not exit cleanly.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// Open the journal in `dir`, returning the commands left behind by the previous session
+    pub fn open(dir: PathBuf) -> io::Result<(Self, Vec<String>)> {
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("session.fitch");
+        let previous = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().map(str::to_string).collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => return Err(error),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok((Self { path, file }, previous))
+    }
+
+    pub fn record(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", line.trim())?;
+        self.file.sync_data()
+    }
+
+    /// Forget the session, e.g. when the user quits or does not want to restore it
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file = File::create(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Whether running the command changes the proof, and should be kept in the journal
+pub fn changes_proof(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Rule(..)
+            | Command::Copy(_)
+            | Command::Premise(_)
+            | Command::Assume(_)
+            | Command::Derive(_)
+            | Command::Insert(..)
+            | Command::Edit(..)
+            | Command::Note(..)
+            | Command::Define(..)
+            | Command::Discharge
+            | Command::Undo
+    )
+}
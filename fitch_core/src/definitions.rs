@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use crate::{Error, Proof, Prop, Rule, StepIndex};
+
+/// Abbreviations of formulas, e.g. `A := (p → q) ∧ (q → r)`, in the order they were
+/// defined. An abbreviation is written like a symbol, but stands for its whole formula.
+/// Each abbreviation is kept with the index that the next step of the proof had when it
+/// was defined, so that it can be undone in turn with the steps.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Definitions(Vec<(String, Prop, StepIndex)>);
+
+impl Definitions {
+    pub fn get(&self, name: &str) -> Option<&Prop> {
+        self.iter()
+            .find(|(defined, _)| *defined == name)
+            .map(|(_, prop)| prop)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Prop)> {
+        self.0.iter().map(|(name, prop, _)| (name.as_str(), prop))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Replace every abbreviation in the formula by the formula it stands for
+    pub fn expand(&self, prop: &Prop) -> Prop {
+        if self.is_empty() {
+            return prop.clone();
+        }
+        prop.substitute(&self.substitution())
+    }
+
+    /// Replace every abbreviation in the formulas that the rule is given as arguments
+    pub fn expand_rule(&self, rule: &Rule) -> Rule {
+        if self.is_empty() {
+            return rule.clone();
+        }
+        rule.substitute(&self.substitution())
+    }
+
+    /// The reverse of [`Definitions::expand`]: every part of the formula that is exactly
+    /// the formula of an abbreviation is replaced by the abbreviation. The largest parts
+    /// are folded first, and the abbreviation defined first is used if several fit.
+    pub fn fold(&self, prop: &Prop) -> Prop {
+        if let Some((name, _)) = self.iter().find(|(_, defined)| *defined == prop) {
+            return Prop::symbol(name);
+        }
+        match prop {
+            Prop::And(lhs, rhs) => Prop::and(self.fold(lhs), self.fold(rhs)),
+            Prop::Or(lhs, rhs) => Prop::or(self.fold(lhs), self.fold(rhs)),
+            Prop::Imply(lhs, rhs) => Prop::imply(self.fold(lhs), self.fold(rhs)),
+            prop => prop.clone(),
+        }
+    }
+
+    fn substitution(&self) -> HashMap<String, Prop> {
+        self.iter()
+            .map(|(name, prop)| (name.to_string(), prop.clone()))
+            .collect()
+    }
+}
+
+/// Whether the symbol occurs anywhere in the formula, including the steps of a proof box
+fn mentions(prop: &Prop, name: &str) -> bool {
+    match prop {
+        Prop::Bottom => false,
+        Prop::Symbol(symbol) => symbol == name,
+        Prop::And(lhs, rhs) | Prop::Or(lhs, rhs) | Prop::Imply(lhs, rhs) => {
+            mentions(lhs, name) || mentions(rhs, name)
+        }
+        Prop::ProofBox(subproof) => subproof
+            .steps()
+            .iter()
+            .any(|(_, step)| mentions(step.prop(), name)),
+    }
+}
+
+impl Proof {
+    pub fn definitions(&self) -> &Definitions {
+        &self.definitions
+    }
+
+    /// Define an abbreviation for a formula, which should already have the earlier
+    /// abbreviations expanded. The name may not already be defined or be a symbol of the
+    /// proof or of an earlier definition, since the formulas written with it would then be
+    /// ambiguous.
+    pub fn define(&mut self, name: String, prop: Prop) -> Result<(), Error> {
+        let in_use = self
+            .definitions
+            .iter()
+            .any(|(defined, body)| defined == name || mentions(body, &name))
+            || self
+                .context
+                .iter()
+                .flat_map(|scope| scope.steps.values())
+                .any(|step| mentions(step.prop(), &name));
+        if in_use {
+            return Err(Error::AlreadyDefined { name });
+        }
+        if mentions(&prop, &name) {
+            return Err(Error::RecursiveDefinition { name });
+        }
+
+        let next = StepIndex(self.index_counter.start);
+        self.definitions.0.push((name, prop, next));
+        Ok(())
+    }
+
+    /// Remove the latest abbreviation if no step has been added since it was defined, i.e.
+    /// if it is the latest change to the proof that [`Proof::undo`] can take back
+    pub(crate) fn undo_definition(&mut self) -> bool {
+        let next = StepIndex(self.index_counter.start);
+        match self.definitions.0.last() {
+            Some((_, _, defined_before)) if *defined_before >= next => {
+                self.definitions.0.pop();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expanding_and_folding() {
+        let (p, q, r) = (Prop::symbol("p"), Prop::symbol("q"), Prop::symbol("r"));
+        let chain = Prop::and(Prop::imply(p.clone(), q.clone()), Prop::imply(q, r.clone()));
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.define("A".to_string(), chain.clone()).unwrap();
+
+        let definitions = proof.definitions();
+        let prop = Prop::imply(Prop::symbol("A"), Prop::imply(p.clone(), r));
+        let expanded = definitions.expand(&prop);
+        assert_eq!(
+            expanded,
+            Prop::imply(chain.clone(), Prop::imply(p.clone(), Prop::symbol("r")))
+        );
+        assert_eq!(definitions.fold(&expanded), prop);
+        assert_eq!(
+            definitions.expand_rule(&Rule::LawOfExcludedMiddle(Prop::symbol("A"))),
+            Rule::LawOfExcludedMiddle(chain.clone())
+        );
+
+        assert!(matches!(
+            proof.define("A".to_string(), p.clone()),
+            Err(Error::AlreadyDefined { .. })
+        ));
+        assert!(matches!(
+            proof.define("p".to_string(), chain),
+            Err(Error::AlreadyDefined { .. })
+        ));
+        // `q` is only a symbol of the definition, but would make `A` ambiguous
+        assert!(matches!(
+            proof.define("q".to_string(), p.clone()),
+            Err(Error::AlreadyDefined { .. })
+        ));
+        assert!(matches!(
+            proof.define("B".to_string(), Prop::and(p, Prop::symbol("B"))),
+            Err(Error::RecursiveDefinition { .. })
+        ));
+    }
+
+    #[test]
+    fn definitions_are_undone_in_turn_with_steps() {
+        let p = Prop::symbol("p");
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.define("A".to_string(), p.clone()).unwrap();
+        proof.copy(StepIndex(1)).unwrap();
+        proof.define("B".to_string(), p.clone()).unwrap();
+
+        proof.undo();
+        assert!(proof.definitions().get("B").is_none());
+        assert_eq!(proof.len(), 2);
+        proof.undo();
+        assert!(proof.definitions().get("A").is_some());
+        assert_eq!(proof.len(), 1);
+        proof.undo();
+        assert!(proof.definitions().is_empty());
+        assert_eq!(proof.len(), 1);
+
+        // The same name can be defined again once its definition is undone
+        proof.define("A".to_string(), Prop::negated(p)).unwrap();
+    }
+}
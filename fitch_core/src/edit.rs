@@ -0,0 +1,446 @@
+use crate::{Error, Proof, Prop, Step, StepIndex, StepType, Violation};
+
+/// A step of the proof when its proof boxes are flattened. A proof box starts with the
+/// step of its assumption and ends with `Close`, unless it is still open.
+enum Event<'a> {
+    Step(StepIndex, &'a Step),
+    Close,
+}
+
+impl Proof {
+    /// Every step of the proof and where its proof boxes end, in order
+    fn events(&self) -> Vec<Event<'_>> {
+        fn flatten<'a>(steps: &[(StepIndex, &'a Step)], events: &mut Vec<Event<'a>>) {
+            for (index, step) in steps {
+                match step.prop() {
+                    Prop::ProofBox(subproof) => {
+                        let steps = subproof
+                            .steps()
+                            .iter()
+                            .map(|(i, s)| (*i, s))
+                            .collect::<Vec<_>>();
+                        flatten(&steps, events);
+                        events.push(Event::Close);
+                    }
+                    _ => events.push(Event::Step(*index, step)),
+                }
+            }
+        }
+
+        let mut events = vec![];
+        for scope in &self.context {
+            let mut steps = scope.steps.iter().map(|(i, s)| (*i, s)).collect::<Vec<_>>();
+            steps.sort_by_key(|(i, _)| *i);
+            flatten(&steps, &mut events);
+        }
+        events
+    }
+
+    /// The premises that follow a step that isn't a premise, or that are inside a proof box
+    pub(crate) fn misplaced_premises(&self) -> Vec<StepIndex> {
+        let mut misplaced = vec![];
+        let (mut depth, mut derived) = (0, false);
+        for event in self.events() {
+            match event {
+                Event::Step(index, step) => match step.step_type() {
+                    StepType::Premise if depth > 0 || derived => misplaced.push(index),
+                    StepType::Premise => (),
+                    StepType::Assumption => {
+                        depth += 1;
+                        derived = true;
+                    }
+                    StepType::Rule(_) | StepType::Copy(_) => derived = true,
+                },
+                Event::Close => depth -= 1,
+            }
+        }
+        misplaced
+    }
+
+    /// The proof up to the given step, which is where it was among the events
+    fn before<'a>(&self, events: &[Event<'a>], target: StepIndex) -> Result<(Proof, usize), Error> {
+        let split = events
+            .iter()
+            .position(|event| matches!(event, Event::Step(index, _) if *index == target))
+            .ok_or(Error::InvalidStepIndex { index: target })?;
+
+        let mut proof = Proof::with_rules(self.allowed_rules.clone());
+        proof.note = self.note.clone();
+        proof.definitions = self.definitions.clone();
+        for event in &events[..split] {
+            proof.push_event(event);
+        }
+        Ok((proof, split))
+    }
+
+    /// Add a step or close a proof box, as it was in the proof that the event is from
+    fn push_event(&mut self, event: &Event) {
+        match event {
+            Event::Step(_, step) => {
+                self.push_unchecked((*step).clone());
+            }
+            Event::Close => self
+                .close_scope()
+                .expect("a proof box always opens a new scope"),
+        }
+    }
+
+    /// Insert steps in front of an existing step, in the same proof box. The new steps are
+    /// added by `insert` to the proof as it is just before the step, so they can only cite
+    /// the steps above them, and must close any proof box they open. The steps after them are
+    /// renumbered, and so is every step and proof box that they cite. Premises can only be
+    /// inserted among the premises at the top of the proof.
+    /// If `insert` fails, the proof is left unchanged.
+    pub fn insert_before(
+        &mut self,
+        target: StepIndex,
+        insert: impl FnOnce(&mut Proof) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let events = self.events();
+        let (mut proof, split) = self.before(&events, target)?;
+
+        let depth = proof.context.len();
+        insert(&mut proof)?;
+        if proof.context.len() != depth || proof.len() < target.0 {
+            return Err(Error::InvalidInsertion);
+        }
+
+        let inserted = proof.len() + 1 - target.0;
+        let renumber = |i: StepIndex| {
+            if i >= target {
+                StepIndex(i.0 + inserted)
+            } else {
+                i
+            }
+        };
+        for event in &events[split..] {
+            match event {
+                Event::Step(_, step) => {
+                    let step_type = match step.step_type() {
+                        StepType::Rule(rule) => StepType::Rule(rule.renumber(renumber)),
+                        StepType::Copy(i) => StepType::Copy(renumber(*i)),
+                        step_type => step_type.clone(),
+                    };
+                    let mut renumbered = Step::new(step.prop().clone(), step_type);
+                    renumbered.set_note(step.note().map(String::from));
+                    proof.push_unchecked(renumbered);
+                }
+                Event::Close => proof
+                    .close_scope()
+                    .expect("a proof box always opens a new scope"),
+            }
+        }
+
+        // Premises can only be inserted among the premises, and steps that are derived can't
+        // be inserted before them
+        let misplaced = proof.misplaced_premises();
+        if misplaced.len() > self.misplaced_premises().len() {
+            let index = misplaced.into_iter().find(|index| *index >= target);
+            return Err(Error::MisplacedPremise {
+                index: index.expect("the new misplaced premise is after the target"),
+            });
+        }
+
+        *self = proof;
+        Ok(())
+    }
+
+    /// Replace a step with the one that `replace` adds to the proof as it is just before the
+    /// step. The step must keep its kind: a premise can be given a new formula, an assumption
+    /// can only be replaced by another assumption, as it opens a proof box, and a derived step
+    /// can be given a new justification, but can't become a premise.
+    ///
+    /// Every later step is derived again from its justification, so that it follows the
+    /// change. The steps that can no longer be justified are kept as they were stated and
+    /// returned, like [`Proof::validate`] would. If `replace` fails, the proof is left unchanged.
+    pub fn edit(
+        &mut self,
+        target: StepIndex,
+        replace: impl FnOnce(&mut Proof) -> Result<(), Error>,
+    ) -> Result<Vec<Violation>, Error> {
+        let events = self.events();
+        let (mut proof, split) = self.before(&events, target)?;
+        let Event::Step(_, old) = events[split] else {
+            unreachable!("the target is a step");
+        };
+
+        let depth = proof.context.len();
+        replace(&mut proof)?;
+        let new = proof.context.last().unwrap().steps.get(&target);
+        let same_kind = new.is_some_and(|new| same_kind(old.step_type(), new.step_type()));
+        let is_assumption = old.step_type() == &StepType::Assumption;
+        let expected_depth = if is_assumption { depth + 1 } else { depth };
+        if proof.len() != target.0 || !same_kind || proof.context.len() != expected_depth {
+            return Err(Error::InvalidEdit { index: target });
+        }
+
+        let mut violations = vec![];
+        for event in &events[split + 1..] {
+            let (index, step) = match event {
+                Event::Step(index, step) => (*index, *step),
+                Event::Close => {
+                    proof
+                        .close_scope()
+                        .expect("a proof box always opens a new scope");
+                    continue;
+                }
+            };
+            let derived = match step.step_type() {
+                StepType::Rule(rule) => proof.derive(rule),
+                StepType::Copy(i) => proof.get_prop(*i).cloned(),
+                StepType::Premise | StepType::Assumption => Ok(step.prop().clone()),
+            };
+            match derived {
+                Ok(prop) => {
+                    let mut derived = Step::new(prop, step.step_type().clone());
+                    derived.set_note(step.note().map(String::from));
+                    proof.push_unchecked(derived);
+                }
+                Err(error) => {
+                    violations.push(Violation { index, error });
+                    proof.push_unchecked(step.clone());
+                }
+            }
+        }
+
+        *self = proof;
+        Ok(violations)
+    }
+
+    /// Attach a note to a step, including the steps inside of closed proof boxes, or remove
+    /// it with `None`
+    pub fn set_step_note(&mut self, target: StepIndex, note: Option<String>) -> Result<(), Error> {
+        let events = self.events();
+        let (mut proof, split) = self.before(&events, target)?;
+        if let Event::Step(_, step) = &events[split] {
+            let mut step = (*step).clone();
+            step.set_note(note);
+            proof.push_unchecked(step);
+        }
+        for event in &events[split + 1..] {
+            proof.push_event(event);
+        }
+
+        *self = proof;
+        Ok(())
+    }
+}
+
+/// Whether both steps are premises, assumptions or derived steps
+fn same_kind(old: &StepType, new: &StepType) -> bool {
+    use StepType::*;
+    matches!(
+        (old, new),
+        (Premise, Premise) | (Assumption, Assumption) | (Rule(_) | Copy(_), Rule(_) | Copy(_))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BoxRange, Rule};
+
+    #[test]
+    fn inserting_steps() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.add_assumption(q.clone());
+        proof.copy(StepIndex(1)).unwrap();
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::ImplyI(StepIndex(2))).unwrap();
+
+        // A premise above the proof box, and a whole proof box in front of it
+        proof
+            .insert_before(StepIndex(2), |proof| {
+                proof.add_premise(q.clone());
+                proof.add_assumption(p.clone());
+                proof.close_scope()
+            })
+            .unwrap();
+        assert!(proof.validate().is_ok());
+        assert_eq!(proof.len(), 6);
+        assert_eq!(
+            proof.get_step(StepIndex(6)).unwrap().step_type(),
+            &StepType::Rule(Rule::ImplyI(StepIndex(4)))
+        );
+
+        // Inside of a proof box, where the new step can only cite the steps above it
+        let unchanged = proof.len();
+        assert!(matches!(
+            proof.insert_before(StepIndex(5), |proof| proof.copy(StepIndex(6)).map(|_| ())),
+            Err(Error::InvalidStepIndex { .. })
+        ));
+        assert_eq!(proof.len(), unchanged);
+        proof
+            .insert_before(StepIndex(5), |proof| proof.copy(StepIndex(2)).map(|_| ()))
+            .unwrap();
+        assert!(proof.validate().is_ok());
+        assert!(proof
+            .check_box_range(&BoxRange {
+                start: StepIndex(4),
+                end: StepIndex(6)
+            })
+            .is_ok());
+        assert_eq!(
+            proof.get_step(StepIndex(7)).unwrap().step_type(),
+            &StepType::Rule(Rule::ImplyI(StepIndex(4)))
+        );
+
+        assert!(matches!(
+            proof.insert_before(StepIndex(2), |proof| {
+                proof.add_assumption(p.clone());
+                Ok(())
+            }),
+            Err(Error::InvalidInsertion)
+        ));
+    }
+
+    #[test]
+    fn editing_steps() {
+        let (p, q, r) = (Prop::symbol("p"), Prop::symbol("q"), Prop::symbol("r"));
+        let mut proof = Proof::new();
+        proof.add_premise(Prop::and(p.clone(), q.clone()));
+        proof.add_premise(p.clone());
+        proof.apply_rule(&Rule::AndELhs(StepIndex(1))).unwrap();
+        proof.copy(StepIndex(3)).unwrap();
+
+        // The later steps follow a new premise
+        let violations = proof
+            .edit(StepIndex(1), |proof| {
+                proof.add_premise(Prop::and(r.clone(), q.clone()));
+                Ok(())
+            })
+            .unwrap();
+        assert!(violations.is_empty());
+        assert_eq!(proof.get_prop(StepIndex(4)).unwrap(), &r);
+
+        // ... and steps that can't be justified anymore are reported
+        let violations = proof
+            .edit(StepIndex(1), |proof| {
+                proof.add_premise(q.clone());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].index, StepIndex(3));
+        assert!(proof.validate().is_err());
+
+        // A new justification is checked against the steps above it
+        assert!(matches!(
+            proof.edit(StepIndex(3), |proof| proof.copy(StepIndex(4)).map(|_| ())),
+            Err(Error::InvalidStepIndex { .. })
+        ));
+        let violations = proof
+            .edit(StepIndex(3), |proof| proof.copy(StepIndex(1)).map(|_| ()))
+            .unwrap();
+        assert!(violations.is_empty());
+        assert!(proof.validate().is_ok());
+        assert_eq!(proof.get_prop(StepIndex(4)).unwrap(), &q);
+
+        assert!(matches!(
+            proof.edit(StepIndex(2), |proof| {
+                proof.add_assumption(p);
+                Ok(())
+            }),
+            Err(Error::InvalidEdit { .. })
+        ));
+        // A derived step can't become a premise, which would add to what the proof assumes
+        assert!(matches!(
+            proof.edit(StepIndex(3), |proof| {
+                proof.add_premise(Prop::symbol("z"));
+                Ok(())
+            }),
+            Err(Error::InvalidEdit { .. })
+        ));
+        assert!(matches!(
+            proof.edit(StepIndex(2), |proof| proof.copy(StepIndex(1)).map(|_| ())),
+            Err(Error::InvalidEdit { .. })
+        ));
+    }
+
+    #[test]
+    fn premises_come_first() {
+        let (p, q, r) = (Prop::symbol("p"), Prop::symbol("q"), Prop::symbol("r"));
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.add_premise(q.clone());
+        proof.copy(StepIndex(1)).unwrap();
+
+        // Only premises can be inserted before a premise ...
+        assert!(matches!(
+            proof.insert_before(StepIndex(2), |proof| proof.copy(StepIndex(1)).map(|_| ())),
+            Err(Error::MisplacedPremise {
+                index: StepIndex(3)
+            })
+        ));
+        proof
+            .insert_before(StepIndex(3), |proof| {
+                proof.add_premise(r.clone());
+                Ok(())
+            })
+            .unwrap();
+        // ... and a premise can't be inserted after a derived step
+        assert!(matches!(
+            proof.insert_before(StepIndex(4), |proof| {
+                proof.copy(StepIndex(1))?;
+                proof.add_premise(r.clone());
+                Ok(())
+            }),
+            Err(Error::MisplacedPremise {
+                index: StepIndex(5)
+            })
+        ));
+        assert!(proof.validate().is_ok());
+
+        // Premises after derived steps or inside proof boxes are reported
+        proof.add_premise(r);
+        proof.add_assumption(p);
+        proof.add_premise(q);
+        let violations = proof.validate().unwrap_err();
+        let indices = violations.iter().map(|v| v.index.0).collect::<Vec<_>>();
+        assert_eq!(indices, vec![5, 7]);
+    }
+
+    #[test]
+    fn notes_are_kept() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.add_assumption(q.clone());
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::ImplyI(StepIndex(2))).unwrap();
+
+        // Steps inside of closed proof boxes can have notes too
+        proof
+            .set_step_note(StepIndex(2), Some("weakening".to_string()))
+            .unwrap();
+        proof
+            .set_step_note(StepIndex(3), Some("by →I".to_string()))
+            .unwrap();
+        assert!(proof.set_step_note(StepIndex(4), None).is_err());
+
+        proof
+            .insert_before(StepIndex(1), |proof| {
+                proof.add_premise(q);
+                Ok(())
+            })
+            .unwrap();
+        proof
+            .edit(StepIndex(2), |proof| {
+                proof.add_premise(p);
+                Ok(())
+            })
+            .unwrap();
+        assert!(proof.validate().is_ok());
+        assert_eq!(proof.get_step(StepIndex(4)).unwrap().note(), Some("by →I"));
+        let assumption = proof.events().into_iter().find_map(|event| match event {
+            Event::Step(StepIndex(3), step) => Some(step.clone()),
+            _ => None,
+        });
+        assert_eq!(assumption.unwrap().note(), Some("weakening"));
+
+        proof.set_step_note(StepIndex(4), None).unwrap();
+        assert_eq!(proof.get_step(StepIndex(4)).unwrap().note(), None);
+    }
+}
@@ -1,14 +1,271 @@
-use crate::{Prop, PropVariant, StepIndex};
+use std::fmt;
+
+use crate::{BoxRange, Notation, Prop, PropVariant, Rule, RuleName, StepIndex};
 
-// TODO: Replace Prop and PropVariant Debug with Display
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("I expected '{expected:?}' but you gave me '{got:?}'")]
+    #[error("{}", mismatch_message(.expected, .got, Notation::Unicode, &mark))]
     PropMismatch { expected: Prop, got: Prop },
     #[error("You used an invalid index '{0}'", index.0)]
     InvalidStepIndex { index: StepIndex },
-    #[error("I expected a '{expected:?}' but got the expression '{got:?}'.")]
-    ExpectedPropVariant { expected: PropVariant, got: Prop },
+    /// One of the arguments given to a rule does not have the form that the rule requires.
+    /// The position counts the arguments of the rule from zero, in the order they are written.
+    #[error("{}", invalid_argument_message(*.position, .expected, .got, Notation::Unicode, &mark))]
+    InvalidArgument {
+        position: usize,
+        expected: Expected,
+        got: Prop,
+    },
     #[error("I can't close the outmost scope.")]
     CannotCloseGlobalScope,
+    #[error("A proof box must contain at least one step.")]
+    EmptySubProof,
+    #[error("The proof box starting at '{0}' must begin with an assumption.", index.0)]
+    SubProofWithoutAssumption { index: StepIndex },
+    #[error("Step '{0}' is an assumption but does not open a proof box.", index.0)]
+    MisplacedAssumption { index: StepIndex },
+    #[error("Step '{0}' can't be a premise, since premises come first and outside of proof boxes.", index.0)]
+    MisplacedPremise { index: StepIndex },
+    #[error("Step '{0}' is not one of the given premises, so it must be derived.", index.0)]
+    NotAPremise { index: StepIndex },
+    #[error("Step '{0}' of the skeleton should be the premise '{premise}'.", index.0)]
+    SkeletonPremise { index: StepIndex, premise: Prop },
+    #[error("The skeleton should end with '{conclusion}' outside of proof boxes.")]
+    SkeletonConclusion { conclusion: Prop },
+    #[error("Step '{0}' is the assumption of a proof box, which needs no justification.", index.0)]
+    JustifiedAssumption { index: StepIndex },
+    #[error("I expected step number '{0}' but found '{1}'.", expected.0, got.0)]
+    UnexpectedStepIndex { expected: StepIndex, got: StepIndex },
+    #[error("'{range}' is not the range of a closed proof box.")]
+    InvalidBoxRange { range: BoxRange },
+    #[error("At least one step must be inserted, and every proof box that is opened must also be closed.")]
+    InvalidInsertion,
+    #[error("Step '{0}' must be replaced by a single step of the same kind: a premise by a premise, an assumption by an assumption, and a derived step by a rule or a copy.", index.0)]
+    InvalidEdit { index: StepIndex },
+    #[error("'{name}' is already in use, either as an abbreviation or as a symbol of the proof.")]
+    AlreadyDefined { name: String },
+    #[error("'{name}' can't be defined in terms of itself.")]
+    RecursiveDefinition { name: String },
+    #[error("{}", rule_not_allowed_message(*.rule, Notation::Unicode))]
+    RuleNotAllowed { rule: RuleName },
+    /// No single rule application derives the formula, see [`Proof::find_rule`]. The closest
+    /// applications are given together with the formulas they derive.
+    ///
+    /// [`Proof::find_rule`]: crate::Proof::find_rule
+    #[error("{}", no_rule_found_message(.target, .near_misses, Notation::Unicode))]
+    NoRuleFound {
+        target: Prop,
+        near_misses: Vec<(Rule, Prop)>,
+    },
+}
+
+impl Error {
+    /// The same message as the `Display` implementation, but with propositions and rules
+    /// printed in `notation`
+    pub fn message(&self, notation: Notation) -> String {
+        self.message_with_highlight(notation, &mark)
+    }
+
+    /// The same message as the `Display` implementation, but with propositions printed in
+    /// `notation` and the differing parts of mismatching propositions passed through
+    /// `highlight` rather than marked as `[q]`
+    pub fn message_with_highlight(
+        &self,
+        notation: Notation,
+        highlight: &dyn Fn(&str) -> String,
+    ) -> String {
+        match self {
+            Error::PropMismatch { expected, got } => {
+                mismatch_message(expected, got, notation, highlight)
+            }
+            Error::InvalidArgument {
+                position,
+                expected,
+                got,
+            } => invalid_argument_message(*position, expected, got, notation, highlight),
+            Error::NoRuleFound {
+                target,
+                near_misses,
+            } => no_rule_found_message(target, near_misses, notation),
+            Error::RuleNotAllowed { rule } => rule_not_allowed_message(*rule, notation),
+            Error::InvalidBoxRange { range } if notation == Notation::Ascii => format!(
+                "'{}-{}' is not the range of a closed proof box.",
+                range.start, range.end
+            ),
+            error => error.to_string(),
+        }
+    }
+}
+
+fn mark(s: &str) -> String {
+    format!("[{s}]")
+}
+
+/// Print both propositions with the smallest subterm where they differ highlighted.
+/// If they differ as a whole, highlighting it would only be noise.
+fn highlight_diff(
+    expected: &Prop,
+    got: &Prop,
+    notation: Notation,
+    highlight: &dyn Fn(&str) -> String,
+) -> (String, String) {
+    let path = expected.diff(got);
+    let (expected, got) = (expected.display(notation), got.display(notation));
+    match path {
+        Some(path) if !path.is_empty() => (
+            expected.highlight(&path, highlight).to_string(),
+            got.highlight(&path, highlight).to_string(),
+        ),
+        _ => (expected.to_string(), got.to_string()),
+    }
+}
+
+fn mismatch_message(
+    expected: &Prop,
+    got: &Prop,
+    notation: Notation,
+    highlight: &dyn Fn(&str) -> String,
+) -> String {
+    let (expected, got) = highlight_diff(expected, got, notation, highlight);
+    format!("I expected '{expected}' but you gave me '{got}'")
+}
+
+fn no_rule_found_message(
+    target: &Prop,
+    near_misses: &[(Rule, Prop)],
+    notation: Notation,
+) -> String {
+    let mut message = format!(
+        "No single rule derives '{}' from the steps above.",
+        target.display(notation)
+    );
+    if !near_misses.is_empty() {
+        message.push_str(" The closest are:");
+        for (rule, derived) in near_misses {
+            message.push_str(&format!(
+                "\n  {}, which gives '{}'",
+                rule.display(notation),
+                derived.display(notation)
+            ));
+        }
+    }
+    message
+}
+
+fn rule_not_allowed_message(rule: RuleName, notation: Notation) -> String {
+    format!(
+        "{} may not be used in this proof.",
+        rule.info().label_in(notation)
+    )
+}
+
+fn invalid_argument_message(
+    position: usize,
+    expected: &Expected,
+    got: &Prop,
+    notation: Notation,
+    highlight: &dyn Fn(&str) -> String,
+) -> String {
+    let (expected, got) = match expected {
+        Expected::Prop(expected) => {
+            let (expected, got) = highlight_diff(expected, got, notation, highlight);
+            (format!("'{expected}'"), got)
+        }
+        expected => (
+            expected.describe(notation),
+            got.display(notation).to_string(),
+        ),
+    };
+    format!(
+        "I expected argument {} to be {expected}, but it is '{got}'.",
+        position + 1
+    )
+}
+
+/// A step that could not be justified when checking an entire proof
+#[derive(Debug, thiserror::Error)]
+#[error("Step {index}: {error}")]
+pub struct Violation {
+    pub index: StepIndex,
+    pub error: Error,
+}
+
+impl Violation {
+    /// The same message as the `Display` implementation, but with propositions and rules
+    /// printed in `notation`
+    pub fn message(&self, notation: Notation) -> String {
+        format!("Step {}: {}", self.index, self.error.message(notation))
+    }
+}
+
+/// What a rule expected one of its arguments to be
+#[derive(Debug, Clone)]
+pub enum Expected {
+    /// Exactly this proposition
+    Prop(Prop),
+    /// A description of the expected form, e.g. "a conjunction"
+    Shape(String),
+    /// A proof box that starts with this assumption
+    Assumption(Prop),
+    /// A proof box that concludes this proposition
+    Conclusion(Prop),
+    /// An implication whose antecedent is this proposition
+    Antecedent(Prop),
+}
+
+impl Expected {
+    /// The expected argument as it is written in the messages, with propositions printed in
+    /// `notation`
+    pub fn describe(&self, notation: Notation) -> String {
+        match self {
+            Expected::Prop(prop) => format!("'{}'", prop.display(notation)),
+            Expected::Shape(shape) => shape.clone(),
+            Expected::Assumption(prop) => {
+                format!(
+                    "a proof box with the assumption '{}'",
+                    prop.display(notation)
+                )
+            }
+            Expected::Conclusion(prop) => {
+                format!("a proof box that concludes '{}'", prop.display(notation))
+            }
+            Expected::Antecedent(prop) => format!(
+                "an implication whose antecedent is '{}'",
+                prop.display(notation)
+            ),
+        }
+    }
+}
+
+impl From<Prop> for Expected {
+    fn from(prop: Prop) -> Self {
+        Expected::Prop(prop)
+    }
+}
+
+impl From<PropVariant> for Expected {
+    fn from(variant: PropVariant) -> Self {
+        match variant {
+            PropVariant::Bottom => Expected::Prop(Prop::Bottom),
+            variant => Expected::Shape(variant.to_string()),
+        }
+    }
+}
+
+impl From<String> for Expected {
+    fn from(shape: String) -> Self {
+        Expected::Shape(shape)
+    }
+}
+
+impl From<&str> for Expected {
+    fn from(shape: &str) -> Self {
+        Expected::Shape(shape.to_string())
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe(Notation::Unicode))
+    }
 }
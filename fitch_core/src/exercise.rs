@@ -0,0 +1,290 @@
+use std::fmt;
+
+use crate::{AllowedRules, Notation, Proof, Prop, RuleName, Skeleton, Step, StepIndex, StepType};
+
+/// A claim that the conclusion can be proven from the premises, written `p, q ⊢ r`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequent {
+    pub premises: Vec<Prop>,
+    pub conclusion: Prop,
+}
+
+/// An exercise of a problem set: a sequent to prove, possibly with restrictions on the proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exercise {
+    pub sequent: Sequent,
+    pub rules: AllowedRules,
+    /// The largest number of steps that the proof may have, counting the premises
+    pub max_steps: Option<usize>,
+    /// Given for exercises where every formula of the proof is known and the student only
+    /// justifies the steps
+    pub skeleton: Option<Skeleton>,
+}
+
+/// Why a proof does not (yet) solve an exercise
+#[derive(Debug, thiserror::Error)]
+pub enum Unsolved {
+    #[error("Every proof box must be closed.")]
+    OpenProofBox,
+    #[error("{}", extra_premise_message(*.index, .premise, Notation::Unicode))]
+    ExtraPremise { index: StepIndex, premise: Prop },
+    #[error("{}", disallowed_rule_message(*.index, *.rule, Notation::Unicode))]
+    DisallowedRule { index: StepIndex, rule: RuleName },
+    #[error("{}", wrong_conclusion_message(.expected, .got.as_ref(), Notation::Unicode))]
+    WrongConclusion { expected: Prop, got: Option<Prop> },
+    #[error("The proof has {steps} steps, but it may have at most {max}.")]
+    TooManySteps { steps: usize, max: usize },
+}
+
+impl Unsolved {
+    /// The same message as the `Display` implementation, but with propositions and rules
+    /// printed in `notation`
+    pub fn message(&self, notation: Notation) -> String {
+        match self {
+            Unsolved::ExtraPremise { index, premise } => {
+                extra_premise_message(*index, premise, notation)
+            }
+            Unsolved::DisallowedRule { index, rule } => {
+                disallowed_rule_message(*index, *rule, notation)
+            }
+            Unsolved::WrongConclusion { expected, got } => {
+                wrong_conclusion_message(expected, got.as_ref(), notation)
+            }
+            unsolved => unsolved.to_string(),
+        }
+    }
+}
+
+fn extra_premise_message(index: StepIndex, premise: &Prop, notation: Notation) -> String {
+    format!(
+        "Step {index} is the premise '{}', which is not given by the exercise.",
+        premise.display(notation)
+    )
+}
+
+fn disallowed_rule_message(index: StepIndex, rule: RuleName, notation: Notation) -> String {
+    format!(
+        "Step {index} uses {}, which is not allowed in this exercise.",
+        rule.info().label_in(notation)
+    )
+}
+
+fn wrong_conclusion_message(expected: &Prop, got: Option<&Prop>, notation: Notation) -> String {
+    let expected = expected.display(notation);
+    match got {
+        Some(got) => format!(
+            "The proof should end with '{expected}', not '{}'.",
+            got.display(notation)
+        ),
+        None => format!("The proof should end with '{expected}'."),
+    }
+}
+
+impl Sequent {
+    pub fn display(&self, notation: Notation) -> DisplaySequent<'_> {
+        DisplaySequent {
+            sequent: self,
+            notation,
+        }
+    }
+}
+
+/// See [`Sequent::display`]
+pub struct DisplaySequent<'a> {
+    sequent: &'a Sequent,
+    notation: Notation,
+}
+
+impl fmt::Display for DisplaySequent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let premises = self
+            .sequent
+            .premises
+            .iter()
+            .map(|premise| premise.display(self.notation).to_string())
+            .collect::<Vec<_>>();
+        let turnstile = match self.notation {
+            Notation::Unicode => "⊢",
+            Notation::Ascii => "|-",
+        };
+        if !premises.is_empty() {
+            write!(f, "{} ", premises.join(", "))?;
+        }
+        write!(
+            f,
+            "{turnstile} {}",
+            self.sequent.conclusion.display(self.notation)
+        )
+    }
+}
+
+impl fmt::Display for Sequent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(Notation::Unicode))
+    }
+}
+
+impl Exercise {
+    pub fn new(sequent: Sequent) -> Self {
+        Self {
+            sequent,
+            rules: AllowedRules::All,
+            max_steps: None,
+            skeleton: None,
+        }
+    }
+
+    /// A proof of the exercise with the premises already in place, where only the rules
+    /// allowed by the exercise can be applied
+    pub fn start(&self) -> Proof {
+        let mut proof = Proof::with_rules(self.rules.clone());
+        for premise in &self.sequent.premises {
+            proof.add_premise(premise.clone());
+        }
+        proof
+    }
+
+    /// Check that a step may be justified this way in the exercise, i.e. that the exercise
+    /// allows its rule
+    pub fn check_step(&self, index: StepIndex, step_type: &StepType) -> Result<(), Unsolved> {
+        match step_type {
+            StepType::Rule(rule) if !self.rules.allows(rule.into()) => {
+                Err(Unsolved::DisallowedRule {
+                    index,
+                    rule: rule.into(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the proof ends with the conclusion in its outermost scope, only relies on
+    /// the premises of the exercise and follows its restrictions, even if the proof itself
+    /// was not created with them. The steps themselves are
+    /// not checked again, use [`Proof::validate`] for proofs that were not built step by step.
+    pub fn check(&self, proof: &Proof) -> Result<(), Unsolved> {
+        if proof.context.len() > 1 {
+            return Err(Unsolved::OpenProofBox);
+        }
+
+        let mut steps = proof.context[0]
+            .steps
+            .iter()
+            .map(|(i, step)| (*i, step))
+            .collect::<Vec<_>>();
+        steps.sort_by_key(|(i, _)| *i);
+
+        for (index, step) in all_steps(&steps) {
+            match step.step_type() {
+                StepType::Premise if !self.sequent.premises.contains(step.prop()) => {
+                    return Err(Unsolved::ExtraPremise {
+                        index,
+                        premise: step.prop().clone(),
+                    });
+                }
+                step_type => self.check_step(index, step_type)?,
+            }
+        }
+
+        let conclusion = steps
+            .last()
+            .map(|(_, step)| step.prop())
+            .filter(|prop| !matches!(prop, Prop::ProofBox(_)));
+        if conclusion != Some(&self.sequent.conclusion) {
+            return Err(Unsolved::WrongConclusion {
+                expected: self.sequent.conclusion.clone(),
+                got: conclusion.cloned(),
+            });
+        }
+
+        match self.max_steps {
+            Some(max) if proof.len() > max => Err(Unsolved::TooManySteps {
+                steps: proof.len(),
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Every step of the proof, including the steps inside of proof boxes, in order
+fn all_steps<'a>(steps: &[(StepIndex, &'a Step)]) -> Vec<(StepIndex, &'a Step)> {
+    let mut all = vec![];
+    for (index, step) in steps {
+        match step.prop() {
+            Prop::ProofBox(subproof) => {
+                let steps = subproof
+                    .steps()
+                    .iter()
+                    .map(|(i, s)| (*i, s))
+                    .collect::<Vec<_>>();
+                all.extend(all_steps(&steps));
+            }
+            _ => all.push((*index, *step)),
+        }
+    }
+    all
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    fn and_commutes() -> Exercise {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        Exercise::new(Sequent {
+            premises: vec![Prop::and(p.clone(), q.clone())],
+            conclusion: Prop::and(q, p),
+        })
+    }
+
+    #[test]
+    fn solving_an_exercise() {
+        let exercise = and_commutes();
+        let mut proof = exercise.start();
+        proof.apply_rule(&Rule::AndELhs(StepIndex(1))).unwrap();
+        proof.apply_rule(&Rule::AndERhs(StepIndex(1))).unwrap();
+        assert!(matches!(
+            exercise.check(&proof),
+            Err(Unsolved::WrongConclusion { got: Some(_), .. })
+        ));
+
+        proof
+            .apply_rule(&Rule::AndI(StepIndex(3), StepIndex(2)))
+            .unwrap();
+        assert!(exercise.check(&proof).is_ok());
+
+        let limited = Exercise {
+            max_steps: Some(3),
+            ..exercise.clone()
+        };
+        assert!(matches!(
+            limited.check(&proof),
+            Err(Unsolved::TooManySteps { steps: 4, max: 3 })
+        ));
+
+        let restricted = Exercise {
+            rules: AllowedRules::Only(vec![RuleName::AndI, RuleName::AndELhs]),
+            ..exercise
+        };
+        assert!(matches!(
+            restricted.check(&proof),
+            Err(Unsolved::DisallowedRule {
+                index: StepIndex(3),
+                rule: RuleName::AndERhs
+            })
+        ));
+    }
+
+    #[test]
+    fn premises_must_be_given() {
+        let exercise = and_commutes();
+        let mut proof = Proof::new();
+        proof.add_premise(Prop::and(Prop::symbol("q"), Prop::symbol("p")));
+        assert!(matches!(
+            exercise.check(&proof),
+            Err(Unsolved::ExtraPremise { .. })
+        ));
+    }
+}
@@ -1,14 +1,27 @@
-use crate::{Proof, Prop, Rule, Step, StepIndex, StepType, SubProof};
-use std::fmt::Write;
+use crate::{
+    BoxRange, Definitions, Proof, Prop, Rule, RuleArg, RuleName, Step, StepIndex, StepType,
+    SubProof,
+};
+use std::{collections::HashMap, fmt::Write};
 
 pub fn latex(proof: &Proof) -> Option<String> {
+    latex_with_definitions(proof, &Definitions::default())
+}
+
+/// Like [`latex`], but the parts of formulas that have been defined as abbreviations are
+/// written by their names. The definitions are written as comments above the proof.
+pub fn latex_folded(proof: &Proof) -> Option<String> {
+    latex_with_definitions(proof, proof.definitions())
+}
+
+fn latex_with_definitions(proof: &Proof, definitions: &Definitions) -> Option<String> {
     if proof.context.len() > 1 {
         // You may not have any open proof boxes when generating latex output
         return None;
     }
-    let scope = proof.context.get(0)?;
+    let scope = proof.context.first()?;
     let mut steps = scope.steps.iter().collect::<Vec<_>>();
-    steps.sort_by(|(i1, _), (i2, _)| i1.cmp(i2));
+    steps.sort_by_key(|(i, _)| **i);
 
     let mut result = String::new();
 
@@ -22,16 +35,34 @@ pub fn latex(proof: &Proof) -> Option<String> {
             .as_slice(),
     );
 
-    write!(result, "\\begin{{logicproof}}{{{max_depth}}}\n").unwrap();
-    steps_to_string(&mut result, steps.as_slice(), 0);
-    write!(result, "\\end{{logicproof}}\n").unwrap();
+    if let Some(note) = proof.note() {
+        writeln!(result, "% {}", comment(note)).unwrap();
+    }
+    for (name, prop) in definitions.iter() {
+        writeln!(result, "% {name} := {}", latex_prop(prop)).unwrap();
+    }
+    writeln!(result, "\\begin{{logicproof}}{{{max_depth}}}").unwrap();
+    steps_to_string(
+        &mut result,
+        steps.as_slice(),
+        0,
+        &proof.box_ranges(),
+        definitions,
+    );
+    writeln!(result, "\\end{{logicproof}}").unwrap();
     Some(result)
 }
 
-fn steps_to_string(s: &mut String, steps: &[(&StepIndex, &Step)], indent_level: usize) {
+fn steps_to_string(
+    s: &mut String,
+    steps: &[(&StepIndex, &Step)],
+    indent_level: usize,
+    box_ranges: &HashMap<StepIndex, BoxRange>,
+    definitions: &Definitions,
+) {
     // Add a new sub-proof if we are indented
     if indent_level > 0 {
-        write!(s, "\\begin{{subproof}}\n").unwrap();
+        writeln!(s, "\\begin{{subproof}}").unwrap();
     }
 
     // Print all the steps (any possibly recursively print completed sub-proofs)
@@ -46,23 +77,34 @@ fn steps_to_string(s: &mut String, steps: &[(&StepIndex, &Step)], indent_level:
                         .collect::<Vec<_>>()
                         .as_slice(),
                     indent_level + 1,
+                    box_ranges,
+                    definitions,
                 );
             }
 
             _ => s.push_str(&format!(
-                "{prop} & {step_type}{newline}\n",
-                step_type = latex_step_type(step.step_type()),
-                prop = latex_prop(step.prop()),
-                newline = if i == steps.len() - 1 { "" } else { " \\\\" }
+                "{prop} & {step_type}{newline}{note}\n",
+                step_type = latex_step_type(step.step_type(), box_ranges),
+                prop = latex_prop(&definitions.fold(step.prop())),
+                newline = if i == steps.len() - 1 { "" } else { " \\\\" },
+                note = match step.note() {
+                    Some(note) => format!(" % {}", comment(note)),
+                    None => String::new(),
+                }
             )),
         }
     }
 
     if indent_level > 0 {
-        write!(s, "\\end{{subproof}}\n").unwrap();
+        writeln!(s, "\\end{{subproof}}").unwrap();
     }
 }
 
+/// A note as a LaTeX comment, which ends at the end of the line
+fn comment(note: &str) -> String {
+    note.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 fn max_depth(steps: &[&Step]) -> usize {
     steps
         .iter()
@@ -106,44 +148,30 @@ fn latex_prop(prop: &Prop) -> String {
     }
 }
 
-fn latex_step_type(step_type: &StepType) -> String {
+fn latex_step_type(step_type: &StepType, box_ranges: &HashMap<StepIndex, BoxRange>) -> String {
     use StepType::*;
     match step_type {
-        Rule(rule) => latex_rule(rule),
+        Rule(rule) => latex_rule(rule, box_ranges),
         Copy(i) => format!("copy {i}"),
         Premise => "premise".to_string(),
         Assumption => "assumption".to_string(),
     }
 }
 
-fn latex_rule(rule: &Rule) -> String {
-    use Rule::*;
-    match rule {
-        AndI(i, j) => format!(r"$\land_{{I}}$ {i}, {j}"),
-        AndELhs(i) => format!(r"$\land_{{E_{{LHS}}}}$ {i}"),
-        AndERhs(i) => format!(r"$\land_{{E_{{RHS}}}}$ {i}"),
-        OrILhs(i, prop) => format!(r"$\lor_{{I_{{LHS}}}}$ {i}, {prop}", prop = latex_prop(prop)),
-        OrIRhs(prop, j) => format!(r"$\lor_{{I_{{RHS}}}}$ {prop}, {j}", prop = latex_prop(prop)),
-        OrE {
-            or_prop,
-            lhs_box,
-            rhs_box,
-        } => format!(r"$\lor_E$ {or_prop}, {lhs_box}, {rhs_box}"),
-        NegI(i) => format!(r"$\neg_I$ {i}"),
-        NegE { prop, neg_prop } => format!(r"$\neg_E$ {prop} {neg_prop}"),
-        ImplyI(i) => format!(r"$\to_I$ {i}"),
-        ImplyE {
-            implication,
-            lhs_proof,
-        } => format!(r"$\to_E$ {implication}, {lhs_proof}"),
-        BottomE(i, prop) => format!(r"$\bot_E$ {i}, {prop}", prop = latex_prop(prop)),
-        DoubleNegE(i) => format!(r"$\neg\neg_E$ {i}"),
-        ModusTollens {
-            implication,
-            negated_rhs,
-        } => format!(r"MT {implication} {negated_rhs}"),
-        DoubleNegI(i) => format!(r"$\neg\neg_I$ {i}"),
-        ProofByContradiction(i) => format!(r"PBC {i}"),
-        LawOfExcludedMiddle(i) => format!(r"LEM {i}"),
-    }
+fn latex_rule(rule: &Rule, box_ranges: &HashMap<StepIndex, BoxRange>) -> String {
+    let args = rule
+        .args()
+        .into_iter()
+        .map(|arg| match arg {
+            RuleArg::Step(i) => i.to_string(),
+            RuleArg::ProofBox(i) => match box_ranges.get(&i) {
+                Some(BoxRange { start, end }) => format!("{start}--{end}"),
+                None => i.to_string(),
+            },
+            RuleArg::Prop(prop) => latex_prop(prop),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("{} {args}", RuleName::from(rule).info().latex)
 }
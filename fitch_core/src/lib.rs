@@ -1,13 +1,28 @@
+mod definitions;
+mod edit;
 mod error;
+mod exercise;
 mod latex;
-mod pretty_print_ascii;
 mod proof;
 mod prop;
+mod registry;
+mod render;
 mod rules;
+mod search;
+mod skeleton;
+mod stats;
 
-pub use error::Error;
-pub use latex::latex;
-pub use pretty_print_ascii::print_proof;
+pub use definitions::Definitions;
+pub use error::{Error, Expected, Violation};
+pub use exercise::{DisplaySequent, Exercise, Sequent, Unsolved};
+pub use latex::{latex, latex_folded};
 pub use proof::Proof;
-pub use prop::{Prop, PropVariant, Step, StepIndex, StepType, SubProof};
-pub use rules::{Rule, RuleName};
+pub use prop::{
+    BoxRange, DisplayProp, Notation, Prop, PropRef, PropVariant, Side, Step, StepIndex, StepType,
+    SubProof,
+};
+pub use registry::{ArgKind, Param, RuleInfo, RULES};
+pub use render::{Renderer, TextStyle, Theme};
+pub use rules::{AllowedRules, DisplayRule, Rule, RuleArg, RuleName};
+pub use skeleton::{Skeleton, SkeletonLine};
+pub use stats::{formula_size, ProofStats};
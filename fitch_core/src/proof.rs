@@ -1,6 +1,9 @@
 use std::{collections::HashMap, ops::RangeFrom};
 
-use crate::{Error, Prop, PropVariant, Rule, Step, StepIndex, StepType, SubProof};
+use crate::{
+    AllowedRules, BoxRange, Definitions, Error, Expected, Prop, PropVariant, Rule, RuleName, Step,
+    StepIndex, StepType, SubProof, Violation,
+};
 
 #[derive(Debug)]
 pub(crate) struct Scope {
@@ -19,6 +22,11 @@ impl Scope {
 pub struct Proof {
     pub(crate) context: Vec<Scope>,
     pub(crate) index_counter: RangeFrom<usize>,
+    pub(crate) allowed_rules: AllowedRules,
+    /// A note about the proof as a whole
+    pub(crate) note: Option<String>,
+    /// The abbreviations that have been defined while writing the proof
+    pub(crate) definitions: Definitions,
 }
 
 impl Default for Proof {
@@ -29,9 +37,47 @@ impl Default for Proof {
 
 impl Proof {
     pub fn new() -> Self {
+        Self::with_rules(AllowedRules::All)
+    }
+
+    /// Create an empty proof where only some of the rules may be applied
+    pub fn with_rules(allowed_rules: AllowedRules) -> Self {
         Self {
             context: vec![Scope::new()],
             index_counter: (1usize..),
+            allowed_rules,
+            note: None,
+            definitions: Definitions::default(),
+        }
+    }
+
+    pub fn allowed_rules(&self) -> &AllowedRules {
+        &self.allowed_rules
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    /// Attach a note to the proof as a whole, or remove it with `None`. Notes on single
+    /// steps are set with [`Proof::set_step_note`].
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.note = note;
+    }
+
+    /// Create a proof from steps that have been constructed elsewhere, for example
+    /// proof boxes built with [`SubProof::new`]. The steps are not checked, use
+    /// [`Proof::validate`] to make sure that the proof is actually correct.
+    pub fn from_steps(steps: Vec<(StepIndex, Step)>) -> Self {
+        let next_index = last_index(&steps).map_or(1, |StepIndex(i)| i + 1);
+        Self {
+            context: vec![Scope {
+                steps: steps.into_iter().collect(),
+            }],
+            index_counter: (next_index..),
+            allowed_rules: AllowedRules::All,
+            note: None,
+            definitions: Definitions::default(),
         }
     }
 
@@ -41,7 +87,6 @@ impl Proof {
     }
 
     pub fn add_premise(&mut self, premise: Prop) -> StepIndex {
-        // TODO: ensure that we only can add premises in the beginning
         let index = self.next_index();
         self.context
             .last_mut()
@@ -68,7 +113,7 @@ impl Proof {
         }
 
         let mut scope = self.context.pop().unwrap();
-        let subproof = SubProof::new(scope.steps.drain().collect());
+        let subproof = SubProof::new(scope.steps.drain().collect())?;
         let starting_index = subproof.starting_index();
         let proof_box = Prop::ProofBox(subproof);
 
@@ -85,13 +130,22 @@ impl Proof {
         StepIndex(self.index_counter.next().unwrap())
     }
 
-    fn add_step(&mut self, step: Step) -> StepIndex {
+    pub(crate) fn add_step(&mut self, step: Step) -> StepIndex {
         let index = self.next_index();
         let scope = self.context.last_mut().unwrap();
         scope.steps.insert(index, step);
         index
     }
 
+    /// Add a step as it is stated, without checking its justification. An assumption opens
+    /// a new proof box.
+    pub(crate) fn push_unchecked(&mut self, step: Step) -> StepIndex {
+        if step.step_type() == &StepType::Assumption {
+            self.context.push(Scope::new());
+        }
+        self.add_step(step)
+    }
+
     pub fn get_prop(&self, index: StepIndex) -> Result<&Prop, Error> {
         self.get_step(index).map(|step| step.prop())
     }
@@ -110,245 +164,431 @@ impl Proof {
         } else if scope_level > 0 {
             self.get_step_helper(scope_level - 1, index)
         } else {
-            return Err(Error::InvalidStepIndex { index });
+            Err(Error::InvalidStepIndex { index })
         }
     }
 
+    /// The number of steps, including the steps inside of proof boxes
+    pub fn len(&self) -> usize {
+        self.index_counter.start - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every step that can currently be cited, ordered by index. Steps inside closed proof
+    /// boxes are not accessible, but the boxes themselves are.
+    pub fn accessible_steps(&self) -> Vec<(StepIndex, &Step)> {
+        let mut steps = self
+            .context
+            .iter()
+            .flat_map(|scope| scope.steps.iter().map(|(i, step)| (*i, step)))
+            .collect::<Vec<_>>();
+        steps.sort_by_key(|(i, _)| *i);
+        steps
+    }
+
+    /// Check that a box cited as a range of steps is exactly an accessible, closed proof box
+    pub fn check_box_range(&self, range: &BoxRange) -> Result<(), Error> {
+        match self.get_prop(range.start) {
+            Ok(Prop::ProofBox(subproof)) if subproof.range() == *range => Ok(()),
+            _ => Err(Error::InvalidBoxRange { range: *range }),
+        }
+    }
+
+    /// The ranges of all closed proof boxes, indexed by their first step
+    pub(crate) fn box_ranges(&self) -> HashMap<StepIndex, BoxRange> {
+        fn collect(
+            steps: &mut dyn Iterator<Item = &Step>,
+            ranges: &mut HashMap<StepIndex, BoxRange>,
+        ) {
+            for step in steps {
+                if let Prop::ProofBox(subproof) = step.prop() {
+                    ranges.insert(subproof.starting_index(), subproof.range());
+                    collect(&mut subproof.steps().iter().map(|(_, step)| step), ranges);
+                }
+            }
+        }
+
+        let mut ranges = HashMap::new();
+        for scope in &self.context {
+            collect(&mut scope.steps.values(), &mut ranges);
+        }
+        ranges
+    }
+
     /// Apply an inference rule using the current context and scope
     pub fn apply_rule(&mut self, rule: &Rule) -> Result<StepIndex, Error> {
+        let prop = self.derive(rule)?;
+        Ok(self.add_step(Step::new(prop, StepType::Rule(rule.clone()))))
+    }
+
+    /// The proposition that a rule would derive in the current scope, without adding it
+    pub fn derive(&self, rule: &Rule) -> Result<Prop, Error> {
+        let name = RuleName::from(rule);
+        if !self.allowed_rules.allows(name) {
+            return Err(Error::RuleNotAllowed { rule: name });
+        }
+
         match rule {
-            Rule::AndI(lhs_index, rhs_index) => {
-                let lhs = self.get_prop(*lhs_index)?;
-                let rhs = self.get_prop(*rhs_index)?;
-                let prop = Prop::And(Box::new(lhs.clone()), Box::new(rhs.clone()));
-                Ok(self.add_step(Step::new(prop, StepType::Rule(rule.clone()))))
-            }
-            Rule::AndELhs(prop_index) => {
-                let prop = self.get_prop(*prop_index)?;
-                if let Prop::And(lhs, _) = prop {
-                    let prop = *lhs.clone();
-                    Ok(self.add_step(Step::new(prop, StepType::Rule(rule.clone()))))
-                } else {
-                    Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::And,
-                        got: prop.clone(),
-                    })
-                }
-            }
-            Rule::AndERhs(prop_index) => {
-                let prop = self.get_prop(*prop_index)?;
-                if let Prop::And(_, rhs) = prop {
-                    let prop = *rhs.clone();
-                    Ok(self.add_step(Step::new(prop, StepType::Rule(rule.clone()))))
-                } else {
-                    Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::And,
-                        got: prop.clone(),
-                    })
-                }
+            Rule::AndI(lhs, rhs) => {
+                let lhs = self.get_formula_arg(0, *lhs)?;
+                let rhs = self.get_formula_arg(1, *rhs)?;
+                Ok(Prop::and(lhs.clone(), rhs.clone()))
             }
+            Rule::AndELhs(prop) => match self.get_formula_arg(0, *prop)? {
+                Prop::And(lhs, _) => Ok(Prop::clone(lhs)),
+                got => Err(invalid_arg(0, PropVariant::And, got)),
+            },
+            Rule::AndERhs(prop) => match self.get_formula_arg(0, *prop)? {
+                Prop::And(_, rhs) => Ok(Prop::clone(rhs)),
+                got => Err(invalid_arg(0, PropVariant::And, got)),
+            },
             Rule::ImplyI(proof_box) => {
-                let proof_box_prop = self.get_prop(*proof_box)?;
-                let Prop::ProofBox(subproof) = proof_box_prop else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::ProofBox,
-                        got: proof_box_prop.clone(),
-                    });
-                };
-
-                let prop = Prop::Imply(
-                    Box::new(subproof.assumption().clone()),
-                    Box::new(subproof.derived_prop().clone()),
-                );
-                Ok(self.add_step(Step::new(prop, StepType::Rule(rule.clone()))))
+                let subproof = self.get_box_arg(0, *proof_box)?;
+                Ok(Prop::imply(
+                    subproof.assumption().clone(),
+                    subproof.derived_prop().clone(),
+                ))
             }
             Rule::OrILhs(index, other) => {
-                let prop = self.get_prop(*index)?;
-                let new = Prop::Or(Box::new(prop.clone()), Box::new(other.clone()));
-                Ok(self.add_step(Step::new(new, StepType::Rule(rule.clone()))))
+                let prop = self.get_formula_arg(0, *index)?;
+                Ok(Prop::or(prop.clone(), other.clone()))
             }
             Rule::OrIRhs(other, index) => {
-                let prop = self.get_prop(*index)?;
-                let new = Prop::Or(Box::new(other.clone()), Box::new(prop.clone()));
-                Ok(self.add_step(Step::new(new, StepType::Rule(rule.clone()))))
+                let prop = self.get_formula_arg(1, *index)?;
+                Ok(Prop::or(other.clone(), prop.clone()))
             }
             Rule::OrE {
                 or_prop,
                 lhs_box,
                 rhs_box,
             } => {
-                let or_prop = self.get_prop(*or_prop)?;
-                let lhs_box = self.get_prop(*lhs_box)?;
-                let rhs_box = self.get_prop(*rhs_box)?;
-
-                let Prop::Or(or_lhs, or_rhs) = or_prop else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Or,
-                        got: or_prop.clone(),
-                    });
-                };
-
-                let Prop::ProofBox(lhs_subproof) = lhs_box else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Or,
-                        got: lhs_box.clone(),
-                    });
+                let (or_lhs, or_rhs) = match self.get_formula_arg(0, *or_prop)? {
+                    Prop::Or(lhs, rhs) => (lhs, rhs),
+                    got => return Err(invalid_arg(0, PropVariant::Or, got)),
                 };
+                let lhs_subproof = self.get_box_arg(1, *lhs_box)?;
+                let rhs_subproof = self.get_box_arg(2, *rhs_box)?;
+
+                if lhs_subproof.assumption() != &**or_lhs {
+                    return Err(invalid_box_arg(
+                        1,
+                        Expected::Assumption(Prop::clone(or_lhs)),
+                        lhs_subproof,
+                    ));
+                }
 
-                let Prop::ProofBox(rhs_subproof) = lhs_box else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Or,
-                        got: rhs_box.clone(),
-                    });
-                };
+                if rhs_subproof.assumption() != &**or_rhs {
+                    return Err(invalid_box_arg(
+                        2,
+                        Expected::Assumption(Prop::clone(or_rhs)),
+                        rhs_subproof,
+                    ));
+                }
 
-                check_eq(or_lhs, lhs_subproof.assumption())?;
-                check_eq(or_rhs, rhs_subproof.assumption())?;
-                check_eq(lhs_subproof.derived_prop(), rhs_subproof.derived_prop())?;
+                let conclusion = lhs_subproof.derived_prop();
+                if rhs_subproof.derived_prop() != conclusion {
+                    return Err(invalid_box_arg(
+                        2,
+                        Expected::Conclusion(conclusion.clone()),
+                        rhs_subproof,
+                    ));
+                }
 
-                Ok(self.add_step(Step::new(
-                    lhs_subproof.derived_prop().clone(),
-                    StepType::Rule(rule.clone()),
-                )))
+                Ok(conclusion.clone())
             }
             Rule::NegI(proof_box) => {
-                let proof_box = self.get_prop(*proof_box)?;
-
-                let Prop::ProofBox(subproof) = proof_box else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::ProofBox,
-                        got: proof_box.clone(),
-                    });
-                };
-
-                check_eq(subproof.derived_prop(), &Prop::Bottom)?;
+                let subproof = self.get_box_arg(0, *proof_box)?;
+                if subproof.derived_prop() != &Prop::Bottom {
+                    return Err(invalid_box_arg(
+                        0,
+                        Expected::Conclusion(Prop::Bottom),
+                        subproof,
+                    ));
+                }
 
-                let negated_prop = Prop::Imply(
-                    Box::new(subproof.assumption().clone()),
-                    Box::new(Prop::Bottom),
-                );
-                Ok(self.add_step(Step::new(negated_prop, StepType::Rule(rule.clone()))))
+                Ok(Prop::negated(subproof.assumption().clone()))
             }
             Rule::NegE { prop, neg_prop } => {
-                let prop = self.get_prop(*prop)?;
-                let neg_prop = self.get_prop(*neg_prop)?;
+                let prop = self.get_formula_arg(0, *prop)?;
+                let neg_prop = self.get_formula_arg(1, *neg_prop)?;
 
                 // neg prop := prop -> bottom
-                let Prop::Imply(lhs, rhs) = neg_prop else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Imply,
-                        got: prop.clone(),
-                    });
-                };
-                check_eq(lhs, prop)?;
-                check_eq(rhs, &Prop::Bottom)?;
+                let expected = Prop::negated(prop.clone());
+                if neg_prop != &expected {
+                    return Err(invalid_arg(1, expected, neg_prop));
+                }
 
-                Ok(self.add_step(Step::new(Prop::Bottom, StepType::Rule(rule.clone()))))
+                Ok(Prop::Bottom)
             }
             Rule::BottomE(bottom_prop, prop) => {
-                let bottom_prop = self.get_prop(*bottom_prop)?;
-                check_eq(bottom_prop, &Prop::Bottom)?;
-                Ok(self.add_step(Step::new(prop.clone(), StepType::Rule(rule.clone()))))
+                let bottom_prop = self.get_formula_arg(0, *bottom_prop)?;
+                if bottom_prop != &Prop::Bottom {
+                    return Err(invalid_arg(0, Prop::Bottom, bottom_prop));
+                }
+
+                Ok(prop.clone())
             }
             Rule::DoubleNegE(double_negated_prop) => {
-                let double_negated_prop = self.get_prop(*double_negated_prop)?;
+                let double_negated_prop = self.get_formula_arg(0, *double_negated_prop)?;
 
                 // neg (neg phi) := (neg phi) -> bottom = (phi -> bottom) -> bottom
-                let Prop::Imply(ref prop_bottom, ref bottom) = double_negated_prop else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Imply,
-                        got: double_negated_prop.clone(),
-                    });
-                };
-
-                check_eq(bottom, &Prop::Bottom)?;
-
-                let Prop::Imply(ref prop, ref bottom2) = **prop_bottom else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Imply,
-                        got: *prop_bottom.clone(),
-                    });
-                };
-
-                check_eq(bottom2, &Prop::Bottom)?;
-
-                Ok(self.add_step(Step::new(*prop.clone(), StepType::Rule(rule.clone()))))
+                match double_negated_prop {
+                    Prop::Imply(neg_prop, bottom) if **bottom == Prop::Bottom => {
+                        match &**neg_prop {
+                            Prop::Imply(prop, bottom) if **bottom == Prop::Bottom => {
+                                Ok(Prop::clone(prop))
+                            }
+                            _ => Err(invalid_arg(0, "a double negation", double_negated_prop)),
+                        }
+                    }
+                    _ => Err(invalid_arg(0, "a double negation", double_negated_prop)),
+                }
             }
             Rule::ImplyE {
                 implication,
                 lhs_proof,
             } => {
-                let implication = self.get_prop(*implication)?;
-                let lhs_proof = self.get_prop(*lhs_proof)?;
-
-                let Prop::Imply(lhs, rhs) = implication else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Imply,
-                        got: implication.clone(),
-                    });
+                let lhs_proof = self.get_formula_arg(1, *lhs_proof)?;
+                let (lhs, rhs) = match self.get_formula_arg(0, *implication)? {
+                    Prop::Imply(lhs, rhs) => (lhs, rhs),
+                    got => {
+                        let expected = Expected::Antecedent(lhs_proof.clone());
+                        return Err(invalid_arg(0, expected, got));
+                    }
                 };
 
-                check_eq(lhs, lhs_proof)?;
+                if lhs_proof != &**lhs {
+                    return Err(invalid_arg(1, Prop::clone(lhs), lhs_proof));
+                }
 
-                Ok(self.add_step(Step::new(*rhs.clone(), StepType::Rule(rule.clone()))))
+                Ok(Prop::clone(rhs))
             }
             Rule::ModusTollens {
                 implication,
                 negated_rhs,
             } => {
-                let implication = self.get_prop(*implication)?;
-                let negated_rhs = self.get_prop(*negated_rhs)?;
-
-                let Prop::Imply(lhs, rhs) = implication else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Imply,
-                        got: implication.clone(),
-                    });
+                let (lhs, rhs) = match self.get_formula_arg(0, *implication)? {
+                    Prop::Imply(lhs, rhs) => (lhs, rhs),
+                    got => return Err(invalid_arg(0, PropVariant::Imply, got)),
                 };
+                let negated_rhs = self.get_formula_arg(1, *negated_rhs)?;
 
-                check_eq(negated_rhs, &Prop::negated(*rhs.clone()))?;
+                let expected = Prop::negated(rhs.clone());
+                if negated_rhs != &expected {
+                    return Err(invalid_arg(1, expected, negated_rhs));
+                }
 
-                let neg_lhs = Prop::negated(*lhs.clone());
-                Ok(self.add_step(Step::new(neg_lhs, StepType::Rule(rule.clone()))))
+                Ok(Prop::negated(lhs.clone()))
             }
             Rule::DoubleNegI(prop) => {
-                let prop = self.get_prop(*prop)?;
-                let neg_neg_prop = Prop::negated(Prop::negated(prop.clone()));
-                Ok(self.add_step(Step::new(neg_neg_prop, StepType::Rule(rule.clone()))))
+                let prop = self.get_formula_arg(0, *prop)?;
+                Ok(Prop::negated(Prop::negated(prop.clone())))
             }
             Rule::ProofByContradiction(proof_box) => {
-                let proof_box = self.get_prop(*proof_box)?;
-
-                let Prop::ProofBox(subproof) = proof_box else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::ProofBox,
-                        got: proof_box.clone(),
-                    });
-                };
+                let subproof = self.get_box_arg(0, *proof_box)?;
 
                 // check if the assumption is negated (i.e. has the form phi -> bottom)
-                let Prop::Imply(ref lhs, ref rhs) = subproof.assumption() else {
-                    return Err(Error::ExpectedPropVariant {
-                        expected: PropVariant::Imply,
-                        got: subproof.assumption().clone(),
-                    });
+                let prop = match subproof.assumption() {
+                    Prop::Imply(lhs, rhs) if **rhs == Prop::Bottom => lhs,
+                    _ => {
+                        let expected = "a proof box whose assumption is a negation";
+                        return Err(invalid_box_arg(0, expected, subproof));
+                    }
                 };
-                check_eq(rhs, &Prop::Bottom)?;
 
                 // also check that the proof box ends with bottom
-                check_eq(subproof.derived_prop(), &Prop::Bottom)?;
+                if subproof.derived_prop() != &Prop::Bottom {
+                    return Err(invalid_box_arg(
+                        0,
+                        Expected::Conclusion(Prop::Bottom),
+                        subproof,
+                    ));
+                }
 
-                Ok(self.add_step(Step::new(*lhs.clone(), StepType::Rule(rule.clone()))))
+                Ok(Prop::clone(prop))
             }
             Rule::LawOfExcludedMiddle(prop) => {
                 let neg_prop = Prop::negated(prop.clone());
-                let or_prop = Prop::Or(Box::new(prop.clone()), Box::new(neg_prop));
-                Ok(self.add_step(Step::new(or_prop, StepType::Rule(rule.clone()))))
+                Ok(Prop::or(prop.clone(), neg_prop))
             }
         }
     }
 
+    /// Look up a step given as argument to a rule, which must be a formula rather than a proof box
+    fn get_formula_arg(&self, position: usize, index: StepIndex) -> Result<&Prop, Error> {
+        match self.get_prop(index)? {
+            got @ Prop::ProofBox(_) => Err(invalid_arg(position, "a formula", got)),
+            prop => Ok(prop),
+        }
+    }
+
+    /// Look up a closed proof box given as argument to a rule
+    fn get_box_arg(&self, position: usize, index: StepIndex) -> Result<&SubProof, Error> {
+        match self.get_prop(index)? {
+            Prop::ProofBox(subproof) => Ok(subproof),
+            got => Err(invalid_arg(position, PropVariant::ProofBox, got)),
+        }
+    }
+
+    /// Apply a substitution uniformly to every step, proof box and rule argument.
+    /// The result is a brand new proof where every step has been checked again.
+    pub fn instantiate(&self, map: &HashMap<String, Prop>) -> Result<Proof, Error> {
+        let substituted = Proof {
+            context: self
+                .context
+                .iter()
+                .map(|scope| Scope {
+                    steps: scope
+                        .steps
+                        .iter()
+                        .map(|(i, step)| (*i, step.substitute(map)))
+                        .collect(),
+                })
+                .collect(),
+            index_counter: self.index_counter.clone(),
+            allowed_rules: self.allowed_rules.clone(),
+            note: self.note.clone(),
+            definitions: self.definitions.clone(),
+        };
+
+        let (proof, violations) = substituted.rebuild();
+        match violations.into_iter().next() {
+            Some(violation) => Err(violation.error),
+            None => Ok(proof),
+        }
+    }
+
+    /// Check every step of the proof again: the justification of each step, that it only
+    /// cites steps accessible from its scope, and that the steps and proof boxes are
+    /// numbered in order. All violations are returned, not just the first one.
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let (_, violations) = self.rebuild();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Replay all steps into a new proof, collecting every step that can't be justified
+    fn rebuild(&self) -> (Proof, Vec<Violation>) {
+        let mut proof = Proof::with_rules(self.allowed_rules.clone());
+        proof.note = self.note.clone();
+        proof.definitions = self.definitions.clone();
+        let mut violations = vec![];
+
+        // Every scope after the global one is an open proof box, whose first step is the
+        // assumption that re-opens it during the replay
+        for (level, scope) in self.context.iter().enumerate() {
+            let mut steps = scope.steps.iter().map(|(i, s)| (*i, s)).collect::<Vec<_>>();
+            steps.sort_by_key(|(i, _)| *i);
+            if !proof.replay(&steps, level > 0, &mut violations) {
+                break;
+            }
+        }
+
+        // Premises are replayed as they are, so their placement is checked separately
+        for index in self.misplaced_premises() {
+            let error = Error::MisplacedPremise { index };
+            violations.push(Violation { index, error });
+        }
+        violations.sort_by_key(|violation| violation.index);
+
+        (proof, violations)
+    }
+
+    /// Add the steps to the proof one by one. A step that can't be justified is added as is,
+    /// so that the steps after it can still be checked. Returns false if the numbering of the
+    /// steps is broken, since nothing after that point can be checked in a meaningful way.
+    fn replay(
+        &mut self,
+        steps: &[(StepIndex, &Step)],
+        opens_box: bool,
+        violations: &mut Vec<Violation>,
+    ) -> bool {
+        for (n, (index, step)) in steps.iter().enumerate() {
+            let index = *index;
+            let expected = StepIndex(self.index_counter.start);
+            if index != expected {
+                violations.push(Violation {
+                    index,
+                    error: Error::UnexpectedStepIndex {
+                        expected,
+                        got: index,
+                    },
+                });
+                return false;
+            }
+
+            let result = match (step.prop(), step.step_type()) {
+                (Prop::ProofBox(subproof), _) => {
+                    let steps = subproof
+                        .steps()
+                        .iter()
+                        .map(|(i, s)| (*i, s))
+                        .collect::<Vec<_>>();
+                    if !self.replay(&steps, true, violations) {
+                        return false;
+                    }
+                    self.close_scope()
+                        .expect("a proof box always opens a new scope");
+                    continue;
+                }
+                (_, StepType::Assumption) if opens_box && n == 0 => {
+                    self.push_unchecked((*step).clone());
+                    continue;
+                }
+                (_, StepType::Assumption) => Err(Error::MisplacedAssumption { index }),
+                (_, StepType::Premise) => {
+                    self.push_unchecked((*step).clone());
+                    continue;
+                }
+                (_, StepType::Copy(i)) => self.copy(*i),
+                (_, StepType::Rule(rule)) => self.apply_rule(rule),
+            };
+
+            let error = match result {
+                Ok(derived) => {
+                    let derived = self
+                        .context
+                        .last_mut()
+                        .unwrap()
+                        .steps
+                        .remove(&derived)
+                        .unwrap();
+                    check_eq(derived.prop(), step.prop()).err()
+                }
+                Err(error) => {
+                    // Make sure the index isn't skipped
+                    self.index_counter.start = index.0 + 1;
+                    Some(error)
+                }
+            };
+
+            if let Some(error) = error {
+                violations.push(Violation { index, error });
+            }
+
+            // Keep the step as stated, whether it could be justified or not
+            self.context
+                .last_mut()
+                .unwrap()
+                .steps
+                .insert(index, (*step).clone());
+        }
+
+        true
+    }
+
+    /// Take back the latest step, or the latest abbreviation if it was defined after it
     pub fn undo(&mut self) {
+        if self.undo_definition() {
+            return;
+        }
+
         let next_index = self.index_counter.next().unwrap();
 
         // If we don't have any steps, we can't undo anything, except restart at 1
@@ -380,7 +620,29 @@ impl Proof {
     }
 }
 
-fn check_eq(p: &Prop, q: &Prop) -> Result<(), Error> {
+fn invalid_arg(position: usize, expected: impl Into<Expected>, got: &Prop) -> Error {
+    Error::InvalidArgument {
+        position,
+        expected: expected.into(),
+        got: got.clone(),
+    }
+}
+
+fn invalid_box_arg(position: usize, expected: impl Into<Expected>, got: &SubProof) -> Error {
+    invalid_arg(position, expected, &Prop::ProofBox(got.clone()))
+}
+
+fn last_index(steps: &[(StepIndex, Step)]) -> Option<StepIndex> {
+    steps
+        .iter()
+        .map(|(i, step)| match step.prop() {
+            Prop::ProofBox(subproof) => last_index(subproof.steps()).unwrap_or(*i),
+            _ => *i,
+        })
+        .max()
+}
+
+pub(crate) fn check_eq(p: &Prop, q: &Prop) -> Result<(), Error> {
     if p != q {
         return Err(Error::PropMismatch {
             expected: p.clone(),
@@ -393,6 +655,7 @@ fn check_eq(p: &Prop, q: &Prop) -> Result<(), Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Notation;
 
     #[test]
     fn conjunction_introduction() {
@@ -408,10 +671,7 @@ mod tests {
 
         assert_eq!(
             ctx.get_prop(p_and_q_prop).unwrap(),
-            &Prop::And(
-                Box::new(Prop::Symbol("p".into())),
-                Box::new(Prop::Symbol("q".into()))
-            )
+            &Prop::and(Prop::symbol("p"), Prop::symbol("q"))
         )
     }
 
@@ -435,10 +695,203 @@ mod tests {
 
         assert_eq!(
             ctx.get_prop(p_implies_q_prop).unwrap(),
-            &Prop::Imply(
-                Box::new(Prop::Symbol("p".into())),
-                Box::new(Prop::Symbol("q".into())),
-            )
+            &Prop::imply(Prop::symbol("p"), Prop::symbol("q"))
         );
     }
+
+    #[test]
+    fn box_ranges() {
+        let mut ctx = Proof::new();
+        let q = ctx.add_premise(Prop::symbol("q"));
+        let p = ctx.add_assumption(Prop::symbol("p"));
+        ctx.copy(q).unwrap();
+        ctx.close_scope().unwrap();
+
+        assert!(ctx
+            .check_box_range(&BoxRange {
+                start: p,
+                end: StepIndex(3)
+            })
+            .is_ok());
+        assert!(ctx
+            .check_box_range(&BoxRange {
+                start: p,
+                end: StepIndex(4)
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn rule_errors_point_to_argument() {
+        let mut ctx = Proof::new();
+        let q = ctx.add_premise(Prop::symbol("q"));
+        let p_implies_r = ctx.add_premise(Prop::imply(Prop::symbol("p"), Prop::symbol("r")));
+
+        let error = ctx
+            .apply_rule(&Rule::ImplyE {
+                implication: p_implies_r,
+                lhs_proof: q,
+            })
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::InvalidArgument {
+                position: 1,
+                expected: Expected::Prop(Prop::Symbol(_)),
+                ..
+            }
+        ));
+
+        let error = ctx
+            .apply_rule(&Rule::ImplyE {
+                implication: q,
+                lhs_proof: q,
+            })
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::InvalidArgument {
+                position: 0,
+                expected: Expected::Antecedent(Prop::Symbol(_)),
+                ..
+            }
+        ));
+
+        // The expected proposition is printed in the notation of the message
+        let p = ctx.add_assumption(Prop::symbol("p"));
+        ctx.close_scope().unwrap();
+        let error = ctx.apply_rule(&Rule::NegI(p)).unwrap_err();
+        assert!(error.message(Notation::Ascii).contains("concludes '_|_'"));
+        assert!(error.to_string().contains("concludes '⊥'"));
+    }
+
+    #[test]
+    fn instantiate_proof() {
+        /*
+        ----------------
+        1. p       assume
+        |---------------
+        |2. -p     assume
+        |3. bottom -e 1, 2
+        |---------------
+        4. --p     -i 2
+        ----------------
+        5. p -> --p ->i 1
+        */
+        let p = Prop::Symbol("p".into());
+
+        let mut ctx = Proof::new();
+        let assumption = ctx.add_assumption(p.clone());
+        let neg_assumption = ctx.add_assumption(Prop::negated(p.clone()));
+        ctx.apply_rule(&Rule::NegE {
+            prop: assumption,
+            neg_prop: neg_assumption,
+        })
+        .unwrap();
+        ctx.close_scope().unwrap();
+        ctx.apply_rule(&Rule::NegI(neg_assumption)).unwrap();
+        ctx.close_scope().unwrap();
+        let result = ctx.apply_rule(&Rule::ImplyI(assumption)).unwrap();
+        assert!(ctx.validate().is_ok());
+
+        let a_and_b = Prop::and(Prop::symbol("a"), Prop::symbol("b"));
+        let map = HashMap::from([("p".to_string(), a_and_b.clone())]);
+        let instance = ctx.instantiate(&map).unwrap();
+
+        assert_eq!(
+            instance.get_prop(result).unwrap(),
+            &Prop::imply(a_and_b.clone(), Prop::negated(Prop::negated(a_and_b)))
+        );
+    }
+
+    #[test]
+    fn restricted_rules() {
+        let mut proof =
+            Proof::with_rules(AllowedRules::AllExcept(vec![RuleName::LawOfExcludedMiddle]));
+        let p = proof.add_premise(Prop::symbol("p"));
+        assert!(matches!(
+            proof.apply_rule(&Rule::LawOfExcludedMiddle(Prop::symbol("q"))),
+            Err(Error::RuleNotAllowed {
+                rule: RuleName::LawOfExcludedMiddle
+            })
+        ));
+        assert!(proof.apply_rule(&Rule::DoubleNegI(p)).is_ok());
+
+        let mut proof = Proof::with_rules(AllowedRules::Only(vec![RuleName::AndI]));
+        let p = proof.add_premise(Prop::symbol("p"));
+        assert!(proof.apply_rule(&Rule::AndI(p, p)).is_ok());
+        assert!(proof.apply_rule(&Rule::DoubleNegI(p)).is_err());
+    }
+
+    #[test]
+    fn validate_external_proof() {
+        /*
+        1. p       premise
+        2. q       premise
+        ----------------
+        3. r       assume
+        4. p       copy 1
+        ----------------
+        5. p ∧ p   ∧i 1, 4  (4 is not accessible)
+        6. q ∧ p   ∧i 1, 2  (gives p ∧ q)
+        7. p ∧ q   ∧i 1, 2
+        */
+        let p = Prop::symbol("p");
+        let q = Prop::symbol("q");
+        let r = Prop::symbol("r");
+
+        assert!(matches!(SubProof::new(vec![]), Err(Error::EmptySubProof)));
+        assert!(matches!(
+            SubProof::new(vec![(
+                StepIndex(3),
+                Step::new(r.clone(), StepType::Premise)
+            )]),
+            Err(Error::SubProofWithoutAssumption { .. })
+        ));
+
+        let subproof = SubProof::new(vec![
+            (StepIndex(3), Step::new(r, StepType::Assumption)),
+            (
+                StepIndex(4),
+                Step::new(p.clone(), StepType::Copy(StepIndex(1))),
+            ),
+        ])
+        .unwrap();
+
+        let and_i = |i, j| StepType::Rule(Rule::AndI(StepIndex(i), StepIndex(j)));
+        let proof = Proof::from_steps(vec![
+            (StepIndex(1), Step::new(p.clone(), StepType::Premise)),
+            (StepIndex(2), Step::new(q.clone(), StepType::Premise)),
+            (
+                StepIndex(3),
+                Step::new(Prop::ProofBox(subproof), StepType::Assumption),
+            ),
+            (
+                StepIndex(5),
+                Step::new(Prop::and(p.clone(), p.clone()), and_i(1, 4)),
+            ),
+            (
+                StepIndex(6),
+                Step::new(Prop::and(q.clone(), p.clone()), and_i(1, 2)),
+            ),
+            (StepIndex(7), Step::new(Prop::and(p, q), and_i(1, 2))),
+        ]);
+
+        let violations = proof.validate().unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert!(matches!(
+            violations[0],
+            Violation {
+                index: StepIndex(5),
+                error: Error::InvalidStepIndex { .. }
+            }
+        ));
+        assert!(matches!(
+            violations[1],
+            Violation {
+                index: StepIndex(6),
+                error: Error::PropMismatch { .. }
+            }
+        ));
+    }
 }
@@ -1,25 +1,48 @@
-use crate::Rule;
-use std::fmt;
+use crate::{Error, Rule, RULES};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::{BuildHasher, Hash, Hasher, RandomState},
+    ops::Deref,
+    ptr,
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+};
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Prop {
     Bottom,
     Symbol(String),
-    And(Box<Prop>, Box<Prop>),
-    Or(Box<Prop>, Box<Prop>),
-    Imply(Box<Prop>, Box<Prop>),
+    And(PropRef, PropRef),
+    Or(PropRef, PropRef),
+    Imply(PropRef, PropRef),
     ProofBox(SubProof),
 }
 
-// TODO: Would be really nice if a subproof could uphold the invariant that it must start with an assumption step
+/// A shared reference to an interned proposition.
+///
+/// Structurally equal propositions are always represented by the same allocation, so
+/// cloning a `PropRef` is just a reference count increment and comparing or hashing two
+/// of them only looks at the pointers.
+#[derive(Clone)]
+pub struct PropRef(Arc<Prop>);
+
+/// The steps of a closed proof box. It is never empty and always starts with an assumption.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct SubProof(pub(crate) Vec<(StepIndex, Step)>);
+pub struct SubProof(pub(crate) Arc<[(StepIndex, Step)]>);
 
 #[derive(Hash, PartialEq, Eq, Clone, Copy, Debug, PartialOrd, Ord)]
 pub struct StepIndex(pub usize);
 
+/// The steps spanned by a closed proof box, cited as `start–end`
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct BoxRange {
+    pub start: StepIndex,
+    pub end: StepIndex,
+}
+
+/// A formula, how it was justified, and optionally a note that explains the step
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Step(Prop, StepType);
+pub struct Step(Prop, StepType, Option<String>);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum StepType {
@@ -29,7 +52,7 @@ pub enum StepType {
     Assumption,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum PropVariant {
     Bottom,
     Symbol,
@@ -39,34 +62,205 @@ pub enum PropVariant {
     ProofBox,
 }
 
+/// The smallest number of propositions in a shard of the interner before we bother to look
+/// for unused ones
+const MIN_SHARD_CAPACITY: usize = 256;
+
+/// The number of shards of the interner. Every proposition belongs to one shard, chosen by
+/// its hash, so threads that intern different propositions rarely wait for each other.
+const SHARDS: usize = 16;
+
+struct Shard {
+    props: HashSet<Arc<Prop>>,
+    /// Remove unused propositions once the set grows beyond this size
+    limit: usize,
+}
+
+/// Shared by all threads, so that propositions can be sent between them
+struct Interner {
+    shards: [Mutex<Shard>; SHARDS],
+    hasher: RandomState,
+}
+
+static INTERNER: LazyLock<Interner> = LazyLock::new(|| Interner {
+    shards: std::array::from_fn(|_| {
+        Mutex::new(Shard {
+            props: HashSet::new(),
+            limit: MIN_SHARD_CAPACITY,
+        })
+    }),
+    hasher: RandomState::new(),
+});
+
+impl PropRef {
+    pub fn new(prop: Prop) -> Self {
+        let index = INTERNER.hasher.hash_one(&prop) as usize % SHARDS;
+        // The set is never left half updated, so it can still be used after a panic
+        let mut shard = INTERNER.shards[index]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(shared) = shard.props.get(&prop) {
+            return Self(Arc::clone(shared));
+        }
+
+        if shard.props.len() >= shard.limit {
+            // Only the interner itself is holding on to these, so they can be dropped. A
+            // proposition can only be shared again through its shard, which is locked.
+            shard.props.retain(|prop| Arc::strong_count(prop) > 1);
+            shard.limit = MIN_SHARD_CAPACITY.max(shard.props.len() * 2);
+        }
+
+        let shared = Arc::new(prop);
+        shard.props.insert(Arc::clone(&shared));
+        Self(shared)
+    }
+}
+
+impl Deref for PropRef {
+    type Target = Prop;
+
+    fn deref(&self) -> &Prop {
+        &self.0
+    }
+}
+
+impl AsRef<Prop> for PropRef {
+    fn as_ref(&self) -> &Prop {
+        &self.0
+    }
+}
+
+impl From<Prop> for PropRef {
+    fn from(prop: Prop) -> Self {
+        Self::new(prop)
+    }
+}
+
+impl PartialEq for PropRef {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for PropRef {}
+
+impl Hash for PropRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(Arc::as_ptr(&self.0), state)
+    }
+}
+
+impl fmt::Debug for PropRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for PropRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
 impl Prop {
-    pub fn negated(prop: Self) -> Self {
-        Prop::Imply(Box::new(prop), Box::new(Prop::Bottom))
+    pub fn symbol(name: impl Into<String>) -> Self {
+        Prop::Symbol(name.into())
+    }
+
+    pub fn and(lhs: impl Into<PropRef>, rhs: impl Into<PropRef>) -> Self {
+        Prop::And(lhs.into(), rhs.into())
+    }
+
+    pub fn or(lhs: impl Into<PropRef>, rhs: impl Into<PropRef>) -> Self {
+        Prop::Or(lhs.into(), rhs.into())
+    }
+
+    pub fn imply(lhs: impl Into<PropRef>, rhs: impl Into<PropRef>) -> Self {
+        Prop::Imply(lhs.into(), rhs.into())
+    }
+
+    pub fn negated(prop: impl Into<PropRef>) -> Self {
+        Prop::imply(prop, Prop::Bottom)
+    }
+
+    /// Replace every symbol that occurs in `map` with its corresponding formula.
+    /// All occurrences are replaced simultaneously, so the substituted formulas are
+    /// not substituted again.
+    pub fn substitute(&self, map: &HashMap<String, Prop>) -> Prop {
+        use Prop::*;
+        match self {
+            Bottom => Bottom,
+            Symbol(s) => map.get(s).cloned().unwrap_or_else(|| self.clone()),
+            And(lhs, rhs) => Prop::and(lhs.substitute(map), rhs.substitute(map)),
+            Or(lhs, rhs) => Prop::or(lhs.substitute(map), rhs.substitute(map)),
+            Imply(lhs, rhs) => Prop::imply(lhs.substitute(map), rhs.substitute(map)),
+            ProofBox(subproof) => ProofBox(subproof.substitute(map)),
+        }
     }
 }
 
 impl SubProof {
-    pub fn new(mut steps: Vec<(StepIndex, Step)>) -> Self {
-        steps.sort_by(|(i1, _), (i2, _)| i1.cmp(i2));
-        Self(steps)
+    /// Create a proof box from its steps, which must start with an assumption
+    pub fn new(mut steps: Vec<(StepIndex, Step)>) -> Result<Self, Error> {
+        steps.sort_by_key(|(i, _)| *i);
+
+        let Some((index, first)) = steps.first() else {
+            return Err(Error::EmptySubProof);
+        };
+
+        if first.step_type() != &StepType::Assumption || matches!(first.prop(), Prop::ProofBox(_)) {
+            return Err(Error::SubProofWithoutAssumption { index: *index });
+        }
+
+        Ok(Self(steps.into()))
     }
 
     pub fn assumption(&self) -> &Prop {
-        &self.0[0].1.prop() // TODO: handle empty subproofs
+        self.0[0].1.prop()
     }
 
     pub fn derived_prop(&self) -> &Prop {
-        &self.0.last().unwrap().1.prop() // TODO: handle empty subproofs
+        self.0[self.0.len() - 1].1.prop()
     }
 
     pub fn starting_index(&self) -> StepIndex {
-        self.0[0].0 // TODO: handle empty subproofs
+        self.0[0].0
+    }
+
+    /// The index of the last step in the box, which may be inside a nested box
+    pub fn ending_index(&self) -> StepIndex {
+        let (index, last) = &self.0[self.0.len() - 1];
+        match last.prop() {
+            Prop::ProofBox(subproof) => subproof.ending_index(),
+            _ => *index,
+        }
+    }
+
+    pub fn range(&self) -> BoxRange {
+        BoxRange {
+            start: self.starting_index(),
+            end: self.ending_index(),
+        }
+    }
+
+    pub fn steps(&self) -> &[(StepIndex, Step)] {
+        &self.0
+    }
+
+    pub fn substitute(&self, map: &HashMap<String, Prop>) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|(i, step)| (*i, step.substitute(map)))
+                .collect(),
+        )
     }
 }
 
 impl Step {
     pub fn new(prop: Prop, step_type: StepType) -> Self {
-        Self(prop, step_type)
+        Self(prop, step_type, None)
     }
 
     pub fn prop_owned(self) -> Prop {
@@ -84,6 +278,22 @@ impl Step {
     pub fn step_type(&self) -> &StepType {
         &self.1
     }
+
+    pub fn note(&self) -> Option<&str> {
+        self.2.as_deref()
+    }
+
+    pub fn set_note(&mut self, note: Option<String>) {
+        self.2 = note;
+    }
+
+    pub fn substitute(&self, map: &HashMap<String, Prop>) -> Self {
+        let step_type = match &self.1 {
+            StepType::Rule(rule) => StepType::Rule(rule.substitute(map)),
+            step_type => step_type.clone(),
+        };
+        Self(self.0.substitute(map), step_type, self.2.clone())
+    }
 }
 
 impl From<&Prop> for PropVariant {
@@ -99,59 +309,257 @@ impl From<&Prop> for PropVariant {
     }
 }
 
-impl fmt::Display for Prop {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use Prop::*;
+/// The symbols used when printing a proposition. Both notations can be parsed
+/// back by `fitch_syntax`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Notation {
+    /// `¬p ∧ q ∨ r → ⊥`
+    #[default]
+    Unicode,
+    /// `-p & q | r -> _|_`
+    Ascii,
+}
+
+impl Notation {
+    fn bottom(self) -> &'static str {
         match self {
-            Bottom => write!(f, "⊥"),
-            Symbol(s) => write!(f, "{s}"),
-            And(lhs, rhs) => {
-                match &**lhs {
-                    lhs @ Imply(..) | lhs @ And(..) | lhs @ Or(..) => write!(f, "({lhs})")?,
-                    _ => write!(f, "{lhs}")?,
-                }
+            Notation::Unicode => "⊥",
+            Notation::Ascii => "_|_",
+        }
+    }
+
+    fn neg(self) -> &'static str {
+        match self {
+            Notation::Unicode => "¬",
+            Notation::Ascii => "-",
+        }
+    }
 
-                write!(f, " ∧ ")?;
+    fn and(self) -> &'static str {
+        match self {
+            Notation::Unicode => "∧",
+            Notation::Ascii => "&",
+        }
+    }
 
-                match &**rhs {
-                    rhs @ Imply(..) | rhs @ And(..) | rhs @ Or(..) => write!(f, "({rhs})")?,
-                    _ => write!(f, "{rhs}")?,
-                }
+    fn or(self) -> &'static str {
+        match self {
+            Notation::Unicode => "∨",
+            Notation::Ascii => "|",
+        }
+    }
 
-                Ok(())
-            }
+    fn imply(self) -> &'static str {
+        match self {
+            Notation::Unicode => "→",
+            Notation::Ascii => "->",
+        }
+    }
+}
 
-            Or(lhs, rhs) => {
-                match &**lhs {
-                    lhs @ Imply(..) | lhs @ And(..) | lhs @ Or(..) => write!(f, "({lhs})")?,
-                    _ => write!(f, "{lhs}")?,
-                }
+/// One of the two operands of a binary connective (the negated proposition counts as
+/// the left hand side, since negation is encoded as an implication)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Side {
+    Lhs,
+    Rhs,
+}
+
+/// Binding strength of the connectives, matching the precedence used by the parser
+/// in `fitch_syntax`. Conjunction and disjunction are left associative while
+/// implication is right associative.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Imply,
+    Or,
+    And,
+    Neg,
+    Atom,
+}
+
+impl Prop {
+    /// Format the proposition using the given notation, only adding the
+    /// parentheses that are needed to parse it back to the same proposition
+    pub fn display(&self, notation: Notation) -> DisplayProp<'_> {
+        DisplayProp {
+            prop: self,
+            notation,
+            highlight: None,
+        }
+    }
 
-                write!(f, " ∨ ")?;
+    /// Find the smallest subterm where two propositions differ. The returned path leads from
+    /// the root to that subterm and is the same in both propositions. If the propositions
+    /// are equal there is no difference to point out, and `None` is returned.
+    pub fn diff(&self, other: &Prop) -> Option<Vec<Side>> {
+        use Prop::*;
+        if self == other {
+            return None;
+        }
 
-                match &**rhs {
-                    rhs @ Imply(..) | rhs @ And(..) | rhs @ Or(..) => write!(f, "({rhs})")?,
-                    _ => write!(f, "{rhs}")?,
+        let mut path = vec![];
+        let (mut lhs, mut rhs) = (self, other);
+        loop {
+            match (lhs, rhs) {
+                (And(lhs1, rhs1), And(lhs2, rhs2))
+                | (Or(lhs1, rhs1), Or(lhs2, rhs2))
+                | (Imply(lhs1, rhs1), Imply(lhs2, rhs2)) => {
+                    if lhs1 == lhs2 {
+                        path.push(Side::Rhs);
+                        (lhs, rhs) = (rhs1, rhs2);
+                    } else if rhs1 == rhs2 {
+                        path.push(Side::Lhs);
+                        (lhs, rhs) = (lhs1, lhs2);
+                    } else {
+                        return Some(path);
+                    }
                 }
+                _ => return Some(path),
+            }
+        }
+    }
+
+    fn precedence(&self) -> Precedence {
+        match self {
+            Prop::Imply(_, rhs) if **rhs == Prop::Bottom => Precedence::Neg,
+            Prop::Imply(..) => Precedence::Imply,
+            Prop::Or(..) => Precedence::Or,
+            Prop::And(..) => Precedence::And,
+            Prop::Bottom | Prop::Symbol(_) | Prop::ProofBox(_) => Precedence::Atom,
+        }
+    }
+}
+
+/// See [`Prop::display`]
+#[derive(Clone, Copy)]
+pub struct DisplayProp<'a> {
+    prop: &'a Prop,
+    notation: Notation,
+    highlight: Option<(&'a [Side], Style<'a>)>,
+}
+
+/// Turns the text of a highlighted subterm into its highlighted form
+type Style<'a> = &'a dyn Fn(&str) -> String;
+
+type Path<'a> = Option<&'a [Side]>;
+
+impl<'a> DisplayProp<'a> {
+    /// Highlight the subterm found by following `path`, by passing its text through `style`
+    pub fn highlight(self, path: &'a [Side], style: Style<'a>) -> Self {
+        Self {
+            highlight: Some((path, style)),
+            ..self
+        }
+    }
+
+    fn with_precedence(
+        &self,
+        prop: &Prop,
+        min: Precedence,
+        path: Path,
+        f: &mut dyn fmt::Write,
+    ) -> fmt::Result {
+        if let (Some([]), Some((_, style))) = (path, self.highlight) {
+            let mut s = String::new();
+            self.with_precedence(prop, min, None, &mut s)?;
+            return write!(f, "{}", style(&s));
+        }
+
+        if prop.precedence() < min {
+            write!(f, "(")?;
+            self.without_parens(prop, path, f)?;
+            write!(f, ")")
+        } else {
+            self.without_parens(prop, path, f)
+        }
+    }
 
-                Ok(())
+    fn without_parens(&self, prop: &Prop, path: Path, f: &mut dyn fmt::Write) -> fmt::Result {
+        use Prop::*;
+        let notation = self.notation;
+        let lhs_path = sub_path(path, Side::Lhs);
+        let rhs_path = sub_path(path, Side::Rhs);
+        match prop {
+            Bottom => write!(f, "{}", notation.bottom()),
+            Symbol(s) => write!(f, "{s}"),
+            // Negation is encoded as phi -> bottom
+            Imply(lhs, rhs) if **rhs == Bottom => {
+                write!(f, "{}", notation.neg())?;
+                // `¬e` would be read as the name of a rule rather than as a negated symbol
+                if matches!(&**lhs, Symbol(s) if is_rule_alias(&format!("{}{s}", notation.neg()))) {
+                    write!(f, " ")?;
+                }
+                self.with_precedence(lhs, Precedence::Neg, lhs_path, f)
+            }
+            And(lhs, rhs) => {
+                self.with_precedence(lhs, Precedence::And, lhs_path, f)?;
+                write!(f, " {} ", notation.and())?;
+                self.with_precedence(rhs, Precedence::Neg, rhs_path, f)
+            }
+            Or(lhs, rhs) => {
+                self.with_precedence(lhs, Precedence::Or, lhs_path, f)?;
+                write!(f, " {} ", notation.or())?;
+                self.with_precedence(rhs, Precedence::And, rhs_path, f)
             }
+            Imply(lhs, rhs) => {
+                self.with_precedence(lhs, Precedence::Or, lhs_path, f)?;
+                write!(f, " {} ", notation.imply())?;
+                self.with_precedence(rhs, Precedence::Imply, rhs_path, f)
+            }
+            ProofBox(subproof) => {
+                write!(f, "[")?;
+                self.with_precedence(subproof.assumption(), Precedence::Imply, None, f)?;
+                write!(f, "... ")?;
+                self.with_precedence(subproof.derived_prop(), Precedence::Imply, None, f)?;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+fn is_rule_alias(text: &str) -> bool {
+    RULES.iter().any(|info| info.aliases.contains(&text))
+}
+
+fn sub_path(path: Path, side: Side) -> Path {
+    match path {
+        Some([first, rest @ ..]) if *first == side => Some(rest),
+        _ => None,
+    }
+}
+
+impl fmt::Display for DisplayProp<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.highlight.map(|(path, _)| path);
+        self.with_precedence(self.prop, Precedence::Imply, path, f)
+    }
+}
 
-            Imply(lhs, rhs) => match &**lhs {
-                lhs @ Imply(..) => write!(f, "({lhs}) → {rhs}"),
-                _ => write!(f, "{lhs} → {rhs}"),
-            },
+impl fmt::Display for Prop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(Notation::Unicode))
+    }
+}
 
-            ProofBox(subproof) => write!(
-                f,
-                "[{assumption}... {derived_prop}]",
-                assumption = subproof.assumption(),
-                derived_prop = subproof.derived_prop()
-            ),
+impl fmt::Display for PropVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropVariant::Bottom => write!(f, "{}", Prop::Bottom),
+            PropVariant::Symbol => write!(f, "a symbol"),
+            PropVariant::And => write!(f, "a conjunction"),
+            PropVariant::Or => write!(f, "a disjunction"),
+            PropVariant::Imply => write!(f, "an implication"),
+            PropVariant::ProofBox => write!(f, "a proof box"),
         }
     }
 }
 
+impl fmt::Display for BoxRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}–{}", self.start, self.end)
+    }
+}
+
 impl fmt::Display for StepType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -162,3 +570,60 @@ impl fmt::Display for StepType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_props_are_shared() {
+        let p_and_q = || Prop::and(Prop::symbol("p"), Prop::symbol("q"));
+        let lhs = PropRef::new(Prop::imply(p_and_q(), Prop::Bottom));
+        let rhs = PropRef::new(Prop::negated(p_and_q()));
+
+        assert!(Arc::ptr_eq(&lhs.0, &rhs.0));
+        assert_ne!(lhs, PropRef::new(p_and_q()));
+    }
+
+    #[test]
+    fn props_are_shared_between_threads() {
+        fn sendable<T: Send + Sync>() {}
+        sendable::<crate::Proof>();
+
+        let p_and_q = || PropRef::new(Prop::and(Prop::symbol("p"), Prop::symbol("q")));
+        let here = p_and_q();
+        let there = std::thread::spawn(p_and_q).join().unwrap();
+        assert_eq!(here, there);
+    }
+
+    #[test]
+    fn highlight_smallest_difference() {
+        let expected = Prop::imply(
+            Prop::and(Prop::symbol("p"), Prop::symbol("q")),
+            Prop::symbol("r"),
+        );
+        let got = Prop::imply(
+            Prop::and(Prop::symbol("p"), Prop::symbol("s")),
+            Prop::symbol("r"),
+        );
+
+        let path = expected.diff(&got).unwrap();
+        assert_eq!(path, [Side::Lhs, Side::Rhs]);
+
+        let mark = |s: &str| format!("[{s}]");
+        let show = |prop: &Prop| {
+            prop.display(Notation::Unicode)
+                .highlight(&path, &mark)
+                .to_string()
+        };
+        assert_eq!(show(&expected), "p ∧ [q] → r");
+        assert_eq!(show(&got), "p ∧ [s] → r");
+        assert_eq!(expected.diff(&expected), None);
+
+        let error = crate::Error::PropMismatch { expected, got };
+        assert_eq!(
+            error.message_with_highlight(Notation::Ascii, &mark),
+            "I expected 'p & [q] -> r' but you gave me 'p & [s] -> r'"
+        );
+    }
+}
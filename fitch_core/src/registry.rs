@@ -0,0 +1,382 @@
+use std::fmt;
+
+use crate::{Notation, PropVariant, RuleName};
+
+/// The kind of argument that a rule expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgKind {
+    /// The index of a previous step
+    Step,
+    /// A closed proof box, cited by its first step or its range of steps
+    ProofBox,
+    /// A formula written out in full
+    Prop,
+}
+
+/// A named parameter of a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    /// The outermost connective that the cited step must have, if the rule requires one.
+    /// Note that negations are implications, so `neg phi` has the shape of an implication.
+    pub shape: Option<PropVariant>,
+}
+
+impl Param {
+    /// Whether a step proving a proposition of the given shape could be cited for this parameter
+    pub fn accepts(&self, variant: PropVariant) -> bool {
+        match (self.kind, self.shape) {
+            (ArgKind::Prop, _) => false,
+            (_, Some(shape)) => shape == variant,
+            (_, None) => variant != PropVariant::ProofBox,
+        }
+    }
+}
+
+/// Everything about a rule except how it is checked, which lives in `Proof::apply_rule`
+#[derive(Debug)]
+pub struct RuleInfo {
+    pub name: RuleName,
+    /// Shown next to the steps of a proof, e.g. `→E`
+    pub label: &'static str,
+    /// The label typeset with LaTeX
+    pub latex: &'static str,
+    /// The names that the rule can be written as. The first one is the canonical name.
+    pub aliases: &'static [&'static str],
+    /// The arguments of the rule, in the order they are written
+    pub params: &'static [Param],
+    /// The rule written as a schema, with the premises above the line and the conclusion below
+    pub schema: &'static str,
+    pub description: &'static str,
+    /// Commands, one per line, that build a small proof ending with an application of the rule
+    pub example: &'static str,
+}
+
+impl fmt::Display for ArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgKind::Step => write!(f, "step"),
+            ArgKind::ProofBox => write!(f, "proof box"),
+            ArgKind::Prop => write!(f, "formula"),
+        }
+    }
+}
+
+impl RuleInfo {
+    pub fn canonical_name(&self) -> &'static str {
+        self.aliases[0]
+    }
+
+    /// The label in `notation`. ASCII has no symbols for the connectives, so the rule is
+    /// labelled with its canonical name there.
+    pub fn label_in(&self, notation: Notation) -> &'static str {
+        match notation {
+            Notation::Unicode => self.label,
+            Notation::Ascii => self.canonical_name(),
+        }
+    }
+
+    /// The names that the rule can be written as in `notation`, which leaves out the names
+    /// with Unicode symbols when writing ASCII
+    pub fn aliases_in(&self, notation: Notation) -> impl Iterator<Item = &'static str> {
+        self.aliases
+            .iter()
+            .copied()
+            .filter(move |alias| notation == Notation::Unicode || alias.is_ascii())
+    }
+
+    /// How the rule is written, e.g. `imply_e <phi -> psi> <phi>`
+    pub fn usage(&self) -> String {
+        let mut usage = self.canonical_name().to_string();
+        for param in self.params {
+            usage.push_str(&format!(" <{}>", param.name));
+        }
+        usage
+    }
+}
+
+const fn step(name: &'static str) -> Param {
+    Param {
+        name,
+        kind: ArgKind::Step,
+        shape: None,
+    }
+}
+
+const fn step_of(name: &'static str, shape: PropVariant) -> Param {
+    Param {
+        name,
+        kind: ArgKind::Step,
+        shape: Some(shape),
+    }
+}
+
+const fn proof_box(name: &'static str) -> Param {
+    Param {
+        name,
+        kind: ArgKind::ProofBox,
+        shape: Some(PropVariant::ProofBox),
+    }
+}
+
+const fn prop(name: &'static str) -> Param {
+    Param {
+        name,
+        kind: ArgKind::Prop,
+        shape: None,
+    }
+}
+
+/// All inference rules, in the order they are presented to the user
+pub const RULES: &[RuleInfo] = &[
+    RuleInfo {
+        name: RuleName::AndI,
+        label: "∧I",
+        latex: r"$\land_{I}$",
+        aliases: &["and_i", "∧i", "&i", "^i"],
+        params: &[step("phi"), step("psi")],
+        schema: "\
+phi     psi
+-----------
+phi  ^  psi",
+        description: "Conjunction introduction: if both phi and psi hold, so does phi ^ psi.",
+        example: "premise p\npremise q\nrule &i 1 2",
+    },
+    RuleInfo {
+        name: RuleName::AndELhs,
+        label: "∧E_lhs",
+        latex: r"$\land_{E_{LHS}}$",
+        aliases: &["and_e_lhs", "∧e_lhs", "&e_lhs", "^e_lhs"],
+        params: &[step_of("phi ^ psi", PropVariant::And)],
+        schema: "\
+phi  ^  psi
+-----------
+    phi",
+        description: "Conjunction elimination: the left hand side of a conjunction holds.",
+        example: "premise p & q\nrule &e_lhs 1",
+    },
+    RuleInfo {
+        name: RuleName::AndERhs,
+        label: "∧E_rhs",
+        latex: r"$\land_{E_{RHS}}$",
+        aliases: &["and_e_rhs", "∧e_rhs", "&e_rhs", "^e_rhs"],
+        params: &[step_of("phi ^ psi", PropVariant::And)],
+        schema: "\
+phi  ^  psi
+-----------
+    psi",
+        description: "Conjunction elimination: the right hand side of a conjunction holds.",
+        example: "premise p & q\nrule &e_rhs 1",
+    },
+    RuleInfo {
+        name: RuleName::OrILhs,
+        label: "∨I_lhs",
+        latex: r"$\lor_{I_{LHS}}$",
+        aliases: &["or_i_lhs", "∨i_lhs", "|i_lhs", "vi_lhs"],
+        params: &[step("phi"), prop("psi")],
+        schema: "\
+    phi
+------------
+ phi \\/ psi",
+        description: "Disjunction introduction: any formula psi can be added to the right of phi.",
+        example: "premise p\nrule |i_lhs 1 q",
+    },
+    RuleInfo {
+        name: RuleName::OrIRhs,
+        label: "∨I_rhs",
+        latex: r"$\lor_{I_{RHS}}$",
+        aliases: &["or_i_rhs", "∨i_rhs", "|i_rhs", "vi_rhs"],
+        params: &[prop("phi"), step("psi")],
+        schema: "\
+    psi
+------------
+ phi \\/ psi",
+        description: "Disjunction introduction: any formula phi can be added to the left of psi.",
+        example: "premise q\nrule |i_rhs p 1",
+    },
+    RuleInfo {
+        name: RuleName::OrE,
+        label: "∨E",
+        latex: r"$\lor_E$",
+        aliases: &["or_e", "∨e", "|e", "ve"],
+        params: &[
+            step_of("phi \\/ psi", PropVariant::Or),
+            proof_box("[phi]...chi"),
+            proof_box("[psi]...chi"),
+        ],
+        schema: "\
+phi \\/ psi   [phi]...chi   [psi]...chi
+-------------------------------------
+               chi",
+        description: "Disjunction elimination: if chi follows from both phi and psi, \
+            it follows from phi \\/ psi.",
+        example: "\
+premise p | q
+assume p
+rule |i_rhs q 2
+discharge
+assume q
+rule |i_lhs 4 p
+discharge
+rule |e 1 2-3 4-5",
+    },
+    RuleInfo {
+        name: RuleName::NegI,
+        label: "¬I",
+        latex: r"$\neg_I$",
+        aliases: &["neg_i", "-i", "¬i"],
+        params: &[proof_box("[phi]...bottom")],
+        schema: "\
+ [phi]...bottom
+----------------
+    neg phi",
+        description: "Negation introduction: if phi leads to a contradiction, phi does not hold.",
+        example: "\
+premise -q
+premise p -> q
+assume p
+rule ->e 2 3
+rule -e 4 1
+discharge
+rule -i 3-5",
+    },
+    RuleInfo {
+        name: RuleName::NegE,
+        label: "¬E",
+        latex: r"$\neg_E$",
+        aliases: &["neg_e", "-e", "¬e"],
+        params: &[step("phi"), step_of("neg phi", PropVariant::Imply)],
+        schema: "\
+ phi    neg phi
+----------------
+     bottom",
+        description: "Negation elimination: a formula and its negation is a contradiction.",
+        example: "premise p\npremise -p\nrule -e 1 2",
+    },
+    RuleInfo {
+        name: RuleName::ImplyI,
+        label: "→I",
+        latex: r"$\to_I$",
+        aliases: &["imply_i", "->i", "⇒i", "→i"],
+        params: &[proof_box("[phi]...psi")],
+        schema: "\
+ [phi]...psi
+-------------
+ phi -> psi",
+        description: "Implication introduction: if psi follows from the assumption phi, \
+            then phi -> psi.",
+        example: "assume p & q\nrule &e_lhs 1\ndischarge\nrule ->i 1-2",
+    },
+    RuleInfo {
+        name: RuleName::ImplyE,
+        label: "→E",
+        latex: r"$\to_E$",
+        aliases: &["imply_e", "->e", "⇒e", "→e"],
+        params: &[step_of("phi -> psi", PropVariant::Imply), step("phi")],
+        schema: "\
+ phi -> psi      phi
+---------------------
+         psi",
+        description: "Implication elimination (modus ponens): psi follows from phi -> psi and phi.",
+        example: "premise p -> q\npremise p\nrule ->e 1 2",
+    },
+    RuleInfo {
+        name: RuleName::BottomE,
+        label: "⊥E",
+        latex: r"$\bot_E$",
+        aliases: &["bottom_e"],
+        params: &[step_of("bottom", PropVariant::Bottom), prop("phi")],
+        schema: "\
+   bottom
+------------
+    phi",
+        description: "Bottom elimination: anything follows from a contradiction.",
+        example: "premise bottom\nrule bottom_e 1 p",
+    },
+    RuleInfo {
+        name: RuleName::DoubleNegE,
+        label: "¬¬E",
+        latex: r"$\neg\neg_E$",
+        aliases: &["neg_neg_e", "--e", "¬¬e"],
+        params: &[step_of("neg (neg phi)", PropVariant::Imply)],
+        schema: "\
+ neg (neg phi)
+---------------
+      phi",
+        description: "Double negation elimination: two negations cancel out.",
+        example: "premise --p\nrule --e 1",
+    },
+    RuleInfo {
+        name: RuleName::ModusTollens,
+        label: "MT",
+        latex: "MT",
+        aliases: &["mt", "modus_tollens"],
+        params: &[
+            step_of("phi -> psi", PropVariant::Imply),
+            step_of("neg psi", PropVariant::Imply),
+        ],
+        schema: "\
+ phi -> psi    neg psi
+------------------------
+         neg phi",
+        description: "Modus tollens: if phi implies psi and psi does not hold, neither does phi.",
+        example: "premise p -> q\npremise -q\nrule mt 1 2",
+    },
+    RuleInfo {
+        name: RuleName::DoubleNegI,
+        label: "¬¬I",
+        latex: r"$\neg\neg_I$",
+        aliases: &["neg_neg_i", "--i", "¬¬i"],
+        params: &[step("phi")],
+        schema: "\
+      phi
+---------------
+ neg (neg phi)",
+        description: "Double negation introduction: a formula implies its double negation.",
+        example: "premise p\nrule --i 1",
+    },
+    RuleInfo {
+        name: RuleName::ProofByContradiction,
+        label: "PBC",
+        latex: "PBC",
+        aliases: &["pbc", "proof_by_contradiction"],
+        params: &[proof_box("[neg phi]...bottom")],
+        schema: "\
+ [neg phi]...bottom
+--------------------
+        phi",
+        description: "Proof by contradiction: if neg phi leads to a contradiction, phi holds.",
+        example: "premise --p\nassume -p\nrule -e 2 1\ndischarge\nrule pbc 2-3",
+    },
+    RuleInfo {
+        name: RuleName::LawOfExcludedMiddle,
+        label: "LEM",
+        latex: "LEM",
+        aliases: &["lem", "law_of_excluded_middle"],
+        params: &[prop("phi")],
+        schema: "\
+----------------
+ phi \\/ neg phi",
+        description: "Law of the excluded middle: every formula either holds or does not hold.",
+        example: "rule lem p",
+    },
+];
+
+impl RuleName {
+    pub fn info(&self) -> &'static RuleInfo {
+        RULES
+            .iter()
+            .find(|info| info.name == *self)
+            .expect("every rule is in the registry")
+    }
+
+    /// Whether the rule only holds in classical logic, i.e. is `¬¬E` or one of the rules
+    /// that can only be derived with it
+    pub fn is_classical(self) -> bool {
+        matches!(
+            self,
+            RuleName::DoubleNegE | RuleName::ProofByContradiction | RuleName::LawOfExcludedMiddle
+        )
+    }
+}
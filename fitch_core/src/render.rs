@@ -0,0 +1,672 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, io,
+};
+
+use crate::{
+    skeleton::Item, BoxRange, Definitions, Notation, Proof, Prop, Rule, RuleArg, RuleName,
+    Skeleton, Step, StepIndex, StepType, SubProof,
+};
+
+/// The narrowest that the formula column is made, even if the proof then gets wider than asked for
+const MIN_FORMULA_WIDTH: usize = 12;
+
+/// Shown in place of a justification that has not been filled in
+const BLANK: &str = "____";
+
+/// Marks the lines of a note, so that they are not mistaken for formulas
+const NOTE: &str = "#";
+
+/// The characters that proof boxes are drawn with
+struct Frame {
+    vertical: &'static str,
+    horizontal: &'static str,
+    top_left: &'static str,
+    top_right: &'static str,
+    bottom_left: &'static str,
+    bottom_right: &'static str,
+}
+
+impl Frame {
+    fn new(notation: Notation) -> Self {
+        match notation {
+            Notation::Unicode => Frame {
+                vertical: "│",
+                horizontal: "─",
+                top_left: "┌",
+                top_right: "┐",
+                bottom_left: "└",
+                bottom_right: "┘",
+            },
+            Notation::Ascii => Frame {
+                vertical: "|",
+                horizontal: "-",
+                top_left: "+",
+                top_right: "+",
+                bottom_left: "+",
+                bottom_right: "+",
+            },
+        }
+    }
+}
+
+/// How a piece of the rendered proof is styled: the text is put between a prefix and a
+/// suffix, e.g. ANSI escape codes for a terminal or markup for a web page
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextStyle {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl TextStyle {
+    pub fn new(prefix: impl Into<String>, suffix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+        }
+    }
+
+    fn paint(&self, text: &str) -> String {
+        if text.is_empty() {
+            String::new()
+        } else {
+            format!("{}{text}{}", self.prefix, self.suffix)
+        }
+    }
+}
+
+/// The styles used when rendering a proof. The default theme leaves all text as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Theme {
+    pub index: TextStyle,
+    /// The justification of a step, e.g. a rule and its arguments
+    pub justification: TextStyle,
+    /// The borders of proof boxes
+    pub border: TextStyle,
+    /// The formula of the most recent step
+    pub latest: TextStyle,
+    /// The formulas of the steps that the most recent step was justified by
+    pub cited: TextStyle,
+    /// The notes on the proof and its steps
+    pub note: TextStyle,
+}
+
+/// Renders proofs as text, with boxes drawn around the proof boxes
+///
+/// ```
+/// use fitch_core::{Notation, Proof, Prop, Renderer};
+///
+/// let mut proof = Proof::new();
+/// proof.add_premise(Prop::symbol("p"));
+/// let text = Renderer::new().notation(Notation::Ascii).width(40).render_to_string(&proof);
+/// assert!(text.starts_with("1  p ") && text.ends_with(" premise\n"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    notation: Notation,
+    width: usize,
+    theme: Theme,
+    fold: bool,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            notation: Notation::Unicode,
+            width: 80,
+            theme: Theme::default(),
+            fold: false,
+        }
+    }
+
+    /// With [`Notation::Ascii`] both the boxes and the formulas only use ASCII characters
+    pub fn notation(self, notation: Notation) -> Self {
+        Self { notation, ..self }
+    }
+
+    /// The number of columns that the proof should fit within, if possible. Formulas that
+    /// don't fit are wrapped onto several lines.
+    pub fn width(self, width: usize) -> Self {
+        Self { width, ..self }
+    }
+
+    pub fn theme(self, theme: Theme) -> Self {
+        Self { theme, ..self }
+    }
+
+    /// Write the parts of formulas that have been defined as abbreviations by their names,
+    /// see [`Proof::define`]. The definitions are then listed above the proof.
+    pub fn fold_definitions(self, fold: bool) -> Self {
+        Self { fold, ..self }
+    }
+
+    pub fn render(&self, proof: &Proof, f: &mut impl fmt::Write) -> fmt::Result {
+        let box_ranges = proof.box_ranges();
+        let mut rows = vec![];
+        if let Some(note) = proof.note() {
+            rows.push(Row::Note {
+                depth: 0,
+                text: note.to_string(),
+            });
+        }
+        let unfolded = Definitions::default();
+        let definitions = if self.fold {
+            proof.definitions()
+        } else {
+            &unfolded
+        };
+        for (name, prop) in definitions.iter() {
+            rows.push(Row::Note {
+                depth: 0,
+                text: format!("{name} := {}", prop.display(self.notation)),
+            });
+        }
+        for (level, scope) in proof.context.iter().enumerate() {
+            let mut steps = scope.steps.iter().map(|(i, s)| (*i, s)).collect::<Vec<_>>();
+            steps.sort_by_key(|(i, _)| *i);
+
+            // Every scope after the global one is a proof box that is still open
+            if level > 0 {
+                rows.push(Row::Open(level));
+            }
+            collect_rows(
+                &steps,
+                level,
+                &box_ranges,
+                definitions,
+                self.notation,
+                &mut rows,
+            );
+        }
+
+        let latest = rows
+            .iter()
+            .filter_map(|row| match row {
+                Row::Step { index, .. } => Some(*index),
+                _ => None,
+            })
+            .max();
+        let cited = latest
+            .and_then(|latest| proof.get_step(latest).ok())
+            .map(|step| cited_steps(step.step_type(), &box_ranges))
+            .unwrap_or_default();
+
+        self.render_rows(&rows, latest, &cited, f)
+    }
+
+    /// Render a skeleton proof, with a blank for every justification that is missing
+    pub fn render_skeleton(&self, skeleton: &Skeleton, f: &mut impl fmt::Write) -> fmt::Result {
+        let box_ranges = skeleton.box_ranges();
+        let mut rows = vec![];
+        let mut depth = 0;
+        for item in skeleton.items() {
+            match item {
+                Item::Open => {
+                    depth += 1;
+                    rows.push(Row::Open(depth));
+                }
+                Item::Close => {
+                    rows.push(Row::Close(depth));
+                    depth -= 1;
+                }
+                Item::Step {
+                    index,
+                    prop,
+                    assumption,
+                } => {
+                    let justification = match skeleton.justification(index) {
+                        _ if assumption => StepType::Assumption.to_string(),
+                        Some(step_type) => {
+                            step_type_to_string(step_type, &box_ranges, self.notation)
+                        }
+                        None => BLANK.to_string(),
+                    };
+                    rows.push(Row::Step {
+                        index,
+                        depth,
+                        formula: prop.display(self.notation).to_string(),
+                        justification,
+                    });
+                }
+            }
+        }
+
+        self.render_rows(&rows, None, &HashSet::new(), f)
+    }
+
+    pub fn write(&self, proof: &Proof, w: &mut impl io::Write) -> io::Result<()> {
+        w.write_all(self.render_to_string(proof).as_bytes())
+    }
+
+    pub fn render_to_string(&self, proof: &Proof) -> String {
+        let mut s = String::new();
+        self.render(proof, &mut s).unwrap();
+        s
+    }
+
+    /// Compute the widths of the columns from the content and lay out the rows. Every line
+    /// has the same length, so that the borders of the boxes line up. Formulas that don't fit
+    /// are wrapped onto several lines and justifications may use at most a third of the width.
+    fn render_rows(
+        &self,
+        rows: &[Row],
+        latest: Option<StepIndex>,
+        cited: &HashSet<StepIndex>,
+        f: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        let (notation, width, theme) = (self.notation, self.width, &self.theme);
+        let frame = Frame::new(notation);
+        let steps = rows.iter().filter_map(|row| match row {
+            Row::Step {
+                index,
+                depth,
+                formula,
+                justification,
+            } => Some((index, *depth, formula, justification)),
+            _ => None,
+        });
+        let max_depth = rows
+            .iter()
+            .map(|row| match row {
+                Row::Step { depth, .. }
+                | Row::Note { depth, .. }
+                | Row::Open(depth)
+                | Row::Close(depth) => *depth,
+            })
+            .max()
+            .unwrap_or(0);
+
+        let index_width = steps
+            .clone()
+            .map(|(index, ..)| columns(&index.to_string()))
+            .max()
+            .unwrap_or(1);
+        let justification_width = steps
+            .clone()
+            .map(|(.., justification)| columns(justification))
+            .max()
+            .unwrap_or(0)
+            .min(width / 3);
+        // The columns that are not used by the formula: the index, the borders on both sides,
+        // the justification and the spaces between them
+        let other_columns = |depth: usize| index_width + justification_width + 2 * depth + 4;
+        let total = steps
+            .map(|(_, depth, formula, _)| other_columns(depth) + columns(formula))
+            .max()
+            .unwrap_or(0)
+            .min(width)
+            .max(other_columns(max_depth) + MIN_FORMULA_WIDTH);
+
+        for row in rows {
+            match row {
+                Row::Open(depth) | Row::Close(depth) => {
+                    let (left, right) = match row {
+                        Row::Open(_) => (frame.top_left, frame.top_right),
+                        _ => (frame.bottom_left, frame.bottom_right),
+                    };
+                    let bars = frame.vertical.repeat(depth - 1);
+                    let hline = frame.horizontal.repeat(total - index_width - 1 - 2 * depth);
+                    let border = format!("{bars}{left}{hline}{right}{bars}");
+                    writeln!(f, "{:index_width$} {}", "", theme.border.paint(&border))?;
+                }
+                Row::Step {
+                    index,
+                    depth,
+                    formula,
+                    justification,
+                } => {
+                    let formula_width = total - other_columns(*depth);
+                    let bars = theme.border.paint(&frame.vertical.repeat(*depth));
+                    let formula_style = match Some(*index) {
+                        i if i == latest => &theme.latest,
+                        _ if cited.contains(index) => &theme.cited,
+                        _ => &TextStyle::default(),
+                    };
+                    let justification = elide(justification, justification_width, notation);
+
+                    let lines = wrap(formula, formula_width, notation);
+                    for (n, formula) in lines.iter().enumerate() {
+                        // Only the first line of a wrapped formula has an index and a justification
+                        let (index, justification) = match n {
+                            0 => (index.to_string(), justification.as_str()),
+                            _ => (String::new(), ""),
+                        };
+                        let index = format!("{index:>index_width$}");
+                        let formula_padding = " ".repeat(formula_width - columns(formula));
+                        let justification = format!("{justification:>justification_width$}");
+
+                        write!(
+                            f,
+                            "{} {bars} {}",
+                            theme.index.paint(&index),
+                            formula_style.paint(formula)
+                        )?;
+                        // Leave out trailing whitespace outside of boxes
+                        if *depth > 0 || !justification.trim().is_empty() {
+                            write!(
+                                f,
+                                "{formula_padding} {}",
+                                theme.justification.paint(&justification)
+                            )?;
+                        }
+                        if *depth > 0 {
+                            write!(f, " {bars}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+                Row::Note { depth, text } => {
+                    // A note spans both the formula and the justification column
+                    let note_width = total - index_width - 2 * depth - 3;
+                    let bars = theme.border.paint(&frame.vertical.repeat(*depth));
+                    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    for line in wrap(&format!("{NOTE} {text}"), note_width, notation) {
+                        write!(f, "{:index_width$} {bars} {}", "", theme.note.paint(&line))?;
+                        if *depth > 0 {
+                            let padding = " ".repeat(note_width - columns(&line));
+                            write!(f, "{padding} {bars}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A line of the rendered proof, before the widths of the columns are known
+enum Row {
+    Step {
+        index: StepIndex,
+        depth: usize,
+        formula: String,
+        justification: String,
+    },
+    /// A note on the step above it, or on the whole proof if it comes first
+    Note { depth: usize, text: String },
+    /// The top border of a proof box whose steps are at the given depth
+    Open(usize),
+    /// The bottom border of a proof box whose steps are at the given depth
+    Close(usize),
+}
+
+fn collect_rows(
+    steps: &[(StepIndex, &Step)],
+    depth: usize,
+    box_ranges: &HashMap<StepIndex, BoxRange>,
+    definitions: &Definitions,
+    notation: Notation,
+    rows: &mut Vec<Row>,
+) {
+    for (index, step) in steps {
+        match step.prop() {
+            Prop::ProofBox(SubProof(subproof)) => {
+                let steps = subproof.iter().map(|(i, s)| (*i, s)).collect::<Vec<_>>();
+                rows.push(Row::Open(depth + 1));
+                collect_rows(&steps, depth + 1, box_ranges, definitions, notation, rows);
+                rows.push(Row::Close(depth + 1));
+            }
+            prop => {
+                rows.push(Row::Step {
+                    index: *index,
+                    depth,
+                    formula: definitions.fold(prop).display(notation).to_string(),
+                    justification: step_type_to_string(step.step_type(), box_ranges, notation),
+                });
+                if let Some(note) = step.note() {
+                    rows.push(Row::Note {
+                        depth,
+                        text: note.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// The steps that a step was justified by, including every step of a cited proof box
+fn cited_steps(
+    step_type: &StepType,
+    box_ranges: &HashMap<StepIndex, BoxRange>,
+) -> HashSet<StepIndex> {
+    let args = match step_type {
+        StepType::Rule(rule) => rule.args(),
+        StepType::Copy(i) => vec![RuleArg::Step(*i)],
+        StepType::Premise | StepType::Assumption => vec![],
+    };
+
+    let mut cited = HashSet::new();
+    for arg in args {
+        match arg {
+            RuleArg::Step(i) => {
+                cited.insert(i);
+            }
+            RuleArg::ProofBox(i) => match box_ranges.get(&i) {
+                Some(BoxRange { start, end }) => cited.extend((start.0..=end.0).map(StepIndex)),
+                None => {
+                    cited.insert(i);
+                }
+            },
+            RuleArg::Prop(_) => (),
+        }
+    }
+    cited
+}
+
+/// The number of columns that a string takes up in the terminal
+fn columns(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Split a formula into lines of at most `width` columns, breaking at spaces.
+/// Parts of the formula that are too long by themselves are cut short.
+fn wrap(formula: &str, width: usize, notation: Notation) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in formula.split(' ') {
+        let word = elide(word, width, notation);
+        if !line.is_empty() && columns(&line) + 1 + columns(&word) > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&word);
+    }
+    lines.push(line);
+    lines
+}
+
+fn elide(s: &str, width: usize, notation: Notation) -> String {
+    if columns(s) <= width {
+        return s.to_string();
+    }
+    let ellipsis = match notation {
+        Notation::Unicode => "…",
+        Notation::Ascii => "...",
+    };
+    let mut elided = s
+        .chars()
+        .take(width.saturating_sub(columns(ellipsis)))
+        .collect::<String>();
+    elided.push_str(ellipsis);
+    elided
+}
+
+fn step_type_to_string(
+    step_type: &StepType,
+    box_ranges: &HashMap<StepIndex, BoxRange>,
+    notation: Notation,
+) -> String {
+    match step_type {
+        StepType::Rule(rule) => rule_to_string(rule, box_ranges, notation),
+        step_type => step_type.to_string(),
+    }
+}
+
+/// Like the `Display` implementation of `Rule`, but cites proof boxes by their range.
+/// In ASCII the rules are called by their canonical names, which can be typed as commands.
+fn rule_to_string(
+    rule: &Rule,
+    box_ranges: &HashMap<StepIndex, BoxRange>,
+    notation: Notation,
+) -> String {
+    let info = RuleName::from(rule).info();
+    let mut s = match notation {
+        Notation::Unicode => info.label,
+        Notation::Ascii => info.canonical_name(),
+    }
+    .to_string();
+    for arg in rule.args() {
+        let arg = match arg {
+            RuleArg::Step(i) => i.to_string(),
+            RuleArg::ProofBox(i) => match (box_ranges.get(&i), notation) {
+                (Some(range), Notation::Unicode) => range.to_string(),
+                (Some(BoxRange { start, end }), Notation::Ascii) => format!("{start}-{end}"),
+                (None, _) => i.to_string(),
+            },
+            RuleArg::Prop(prop) => prop.display(notation).to_string(),
+        };
+        s.push(' ');
+        s.push_str(&arg);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_output() {
+        let mut proof = Proof::new();
+        proof.add_premise(Prop::negated(Prop::symbol("q")));
+        proof.add_assumption(Prop::symbol("q"));
+        proof
+            .apply_rule(&Rule::NegE {
+                prop: StepIndex(2),
+                neg_prop: StepIndex(1),
+            })
+            .unwrap();
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::NegI(StepIndex(2))).unwrap();
+
+        let output = Renderer::new()
+            .notation(Notation::Ascii)
+            .render_to_string(&proof);
+        assert!(output.is_ascii(), "{output}");
+        assert!(output.contains("+----"));
+        assert!(output.contains("_|_"));
+        assert!(output.contains("neg_i 2-3"));
+        assert!(!Renderer::new().render_to_string(&proof).is_ascii());
+    }
+
+    #[test]
+    fn long_formulas_are_wrapped() {
+        let long = (0..20)
+            .map(|i| Prop::symbol(format!("p{i}")))
+            .reduce(Prop::and)
+            .unwrap();
+        let mut proof = Proof::new();
+        proof.add_premise(long.clone());
+        proof.add_assumption(long);
+        proof.add_assumption(Prop::symbol("q"));
+
+        let output = Renderer::new().width(40).render_to_string(&proof);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines.len() > 5, "{output}");
+        // The innermost box and its border line up with the box around it
+        let (border, last) = (lines[lines.len() - 2], lines[lines.len() - 1]);
+        assert!(border.ends_with("┐│") && columns(border) == 40, "{output}");
+        assert!(last.ends_with("││") && columns(last) == 40, "{output}");
+    }
+
+    #[test]
+    fn skeletons_have_blanks() {
+        let mut proof = Proof::new();
+        proof.add_assumption(Prop::symbol("p"));
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::ImplyI(StepIndex(1))).unwrap();
+
+        let mut output = String::new();
+        Renderer::new()
+            .notation(Notation::Ascii)
+            .render_skeleton(&Skeleton::from(&proof), &mut output)
+            .unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines[1].contains("assumption"), "{output}");
+        assert!(lines[3].starts_with("2  p -> p") && lines[3].ends_with(BLANK));
+    }
+
+    #[test]
+    fn latest_and_cited_steps_are_styled() {
+        let mut proof = Proof::new();
+        proof.add_premise(Prop::symbol("p"));
+        proof.add_premise(Prop::symbol("q"));
+        proof.add_premise(Prop::symbol("r"));
+        proof
+            .apply_rule(&Rule::AndI(StepIndex(1), StepIndex(3)))
+            .unwrap();
+
+        let theme = Theme {
+            latest: TextStyle::new("<", ">"),
+            cited: TextStyle::new("[", "]"),
+            ..Theme::default()
+        };
+        let output = Renderer::new().theme(theme).render_to_string(&proof);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("1  [p]"), "{output}");
+        assert!(lines[1].starts_with("2  q"), "{output}");
+        assert!(lines[2].starts_with("3  [r]"), "{output}");
+        assert!(lines[3].starts_with("4  <p ∧ r>"), "{output}");
+    }
+
+    #[test]
+    fn notes_are_shown_below_their_steps() {
+        let mut proof = Proof::new();
+        proof.set_note(Some("Currying".to_string()));
+        proof.add_assumption(Prop::symbol("p"));
+        proof
+            .set_step_note(StepIndex(1), Some("the assumption".to_string()))
+            .unwrap();
+
+        let theme = Theme {
+            note: TextStyle::new("<", ">"),
+            ..Theme::default()
+        };
+        let output = Renderer::new()
+            .width(40)
+            .theme(theme)
+            .render_to_string(&proof);
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "   <# Currying>", "{output}");
+        assert!(lines[3].starts_with("  │ <# the assumption>"), "{output}");
+        assert_eq!(columns(lines[3]), columns(lines[2]) + 2, "{output}");
+    }
+
+    #[test]
+    fn definitions_can_be_folded() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof
+            .define("A".to_string(), Prop::imply(p.clone(), q.clone()))
+            .unwrap();
+        proof.add_premise(Prop::and(Prop::imply(p, q.clone()), q));
+
+        let folded = Renderer::new()
+            .fold_definitions(true)
+            .render_to_string(&proof);
+        let lines = folded.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "   # A := p → q", "{folded}");
+        assert!(lines[1].starts_with("1  A ∧ q "), "{folded}");
+        let expanded = Renderer::new().render_to_string(&proof);
+        assert!(expanded.starts_with("1  (p → q) ∧ q "), "{expanded}");
+    }
+}
@@ -1,5 +1,5 @@
-use crate::{Prop, StepIndex};
-use std::fmt;
+use crate::{ArgKind, Notation, Prop, StepIndex};
+use std::{collections::HashMap, fmt};
 
 impl fmt::Display for StepIndex {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -7,260 +7,325 @@ impl fmt::Display for StepIndex {
     }
 }
 
-/// Inference rules from from page 27 in "Logic in Computer Science" by Huth and Ryan
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Rule {
-    /// ```notrust
-    /// phi     psi
-    /// -----------
-    /// phi  ^  psi
-    /// ```
-    AndI(StepIndex, StepIndex),
+/// Declares [`Rule`] and [`RuleName`] from the table below, together with everything that
+/// follows from the operands of a rule alone: its name, its operands in the order they are
+/// written, how it is built from them and how the formulas among them are substituted.
+///
+/// Each rule is written like a variant of [`Rule`], with the kind of each operand, `Index`
+/// for a step or proof box and `Prop` for a formula, in place of its type.
+macro_rules! rules {
+    // Every rule is read into its declaration, a pattern that binds its operands (which
+    // also builds the rule from variables of the same names) and its operands with kinds
+    (@read [$($rules:tt)*]) => {
+        rules!(@define $($rules)*);
+    };
+    (@read [$($rules:tt)*] $name:ident($($operand:ident: $kind:ident),*) $(, $($rest:tt)*)?) => {
+        rules!(@read [
+            $($rules)*
+            {
+                $name
+                [$name($(rules!(@type $kind)),*)]
+                [Rule::$name($($operand),*)]
+                [$($operand $kind)*]
+            }
+        ] $($($rest)*)?);
+    };
+    (@read [$($rules:tt)*] $name:ident { $($operand:ident: $kind:ident),* $(,)? } $(, $($rest:tt)*)?) => {
+        rules!(@read [
+            $($rules)*
+            {
+                $name
+                [$name { $($operand: rules!(@type $kind)),* }]
+                [Rule::$name { $($operand),* }]
+                [$($operand $kind)*]
+            }
+        ] $($($rest)*)?);
+    };
 
-    /// ```notrust
-    /// phi  ^  psi
-    /// -----------
-    ///     phi
-    /// ```
-    AndELhs(StepIndex),
+    (@type Index) => { StepIndex };
+    (@type Prop) => { Prop };
+    (@operand Index $operand:ident) => { Operand::Index(*$operand) };
+    (@operand Prop $operand:ident) => { Operand::Prop($operand) };
+    (@take Index $i:ident $p:ident) => { $i() };
+    (@take Prop $i:ident $p:ident) => { $p() };
+    (@substitute Index $operand:ident $map:ident) => { *$operand };
+    (@substitute Prop $operand:ident $map:ident) => { $operand.substitute($map) };
 
-    /// ```notrust
-    /// phi  ^  psi
-    /// -----------
-    ///     psi
-    /// ```
-    AndERhs(StepIndex),
+    (@define $({ $name:ident [$($decl:tt)*] [$($pattern:tt)*] [$($operand:ident $kind:ident)*] })*) => {
+        /// Inference rules from from page 27 in "Logic in Computer Science" by Huth and Ryan.
+        /// The arguments are stored in the order they are written. Everything else that is
+        /// known about a rule, such as its schema and parameters, is in
+        /// [`RULES`](crate::RULES).
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum Rule {
+            $($($decl)*,)*
+        }
 
-    /// ```notrust
-    ///     phi
-    /// ------------
-    ///  phi \/ psi
-    /// ```
-    OrILhs(StepIndex, Prop),
+        /// The name of a rule without its arguments. See [`RULES`](crate::RULES) for what is
+        /// known about each rule.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum RuleName {
+            $($name,)*
+        }
 
-    /// ```notrust
-    ///     psi
-    /// ------------
-    ///  phi \/ psi
-    /// ```
-    OrIRhs(Prop, StepIndex),
+        impl From<&Rule> for RuleName {
+            fn from(rule: &Rule) -> Self {
+                match rule {
+                    $(Rule::$name { .. } => RuleName::$name,)*
+                }
+            }
+        }
 
-    /// ```notrust
-    /// phi \/ psi   [phi]...chi   [psi]...chi
-    /// -------------------------------------
-    ///                chi
-    /// ```
-    OrE {
-        or_prop: StepIndex,
-        lhs_box: StepIndex,
-        rhs_box: StepIndex,
-    },
+        impl Rule {
+            fn operands(&self) -> Vec<Operand<'_>> {
+                match self {
+                    $($($pattern)* => vec![$(rules!(@operand $kind $operand)),*],)*
+                }
+            }
 
-    /// ```notrust
-    ///  [phi]...bottom
-    /// ----------------
-    ///     neg phi
-    /// ```
-    /// (Note: neg phi = phi -> bottom)
-    NegI(StepIndex),
+            /// Build the rule with the given name, taking the indices and formulas that it
+            /// cites in order from `i` and `p`
+            fn build(
+                name: RuleName,
+                mut i: impl FnMut() -> StepIndex,
+                mut p: impl FnMut() -> Prop,
+            ) -> Rule {
+                match name {
+                    $(RuleName::$name => {
+                        $(let $operand = rules!(@take $kind i p);)*
+                        $($pattern)*
+                    })*
+                }
+            }
 
-    /// ```notrust
-    ///  phi    neg phi
-    /// ----------------
-    ///      bottom
-    /// ```
-    NegE {
-        prop: StepIndex,
-        neg_prop: StepIndex,
-    },
+            /// Apply a substitution to the propositions given as arguments to the rule
+            pub fn substitute(&self, map: &HashMap<String, Prop>) -> Self {
+                match self {
+                    $($($pattern)* => {
+                        $(let $operand = rules!(@substitute $kind $operand map);)*
+                        $($pattern)*
+                    })*
+                }
+            }
+        }
+    };
 
-    /// ```notrust
-    ///  [phi]...psi
-    /// -------------
-    ///      psi
-    /// ```
-    ImplyI(StepIndex),
+    ($($table:tt)*) => {
+        rules!(@read [] $($table)*);
+    };
+}
 
-    /// Modus ponens
-    /// ```notrust
-    ///  phi      phi -> psi
-    /// ---------------------
-    ///          psi
-    /// ```
+// Adding a rule means adding it here, describing it in `RULES` and deriving its conclusion
+// in `Proof::derive`
+rules! {
+    AndI(phi: Index, psi: Index),
+    AndELhs(phi: Index),
+    AndERhs(phi: Index),
+    OrILhs(phi: Index, psi: Prop),
+    OrIRhs(phi: Prop, psi: Index),
+    OrE {
+        or_prop: Index,
+        lhs_box: Index,
+        rhs_box: Index,
+    },
+    NegI(phi: Index),
+    NegE {
+        prop: Index,
+        neg_prop: Index,
+    },
+    ImplyI(phi: Index),
     ImplyE {
-        implication: StepIndex,
-        lhs_proof: StepIndex,
+        implication: Index,
+        lhs_proof: Index,
+    },
+    BottomE(phi: Index, psi: Prop),
+    DoubleNegE(phi: Index),
+    ModusTollens {
+        implication: Index,
+        negated_rhs: Index,
     },
+    DoubleNegI(phi: Index),
+    ProofByContradiction(phi: Index),
+    LawOfExcludedMiddle(phi: Prop),
+}
 
-    /// ```notrust
-    ///    bottom
-    /// ------------
-    ///     phi
-    /// ```
-    BottomE(StepIndex, Prop),
+/// An argument given to a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleArg<'a> {
+    /// A previous step
+    Step(StepIndex),
+    /// A closed proof box, referred to by its first step
+    ProofBox(StepIndex),
+    Prop(&'a Prop),
+}
 
-    /// ```notrust
-    ///  neg (neg phi)
-    /// ---------------
-    ///      phi
-    /// ```
-    DoubleNegE(StepIndex),
+impl RuleArg<'_> {
+    pub fn kind(&self) -> ArgKind {
+        match self {
+            RuleArg::Step(_) => ArgKind::Step,
+            RuleArg::ProofBox(_) => ArgKind::ProofBox,
+            RuleArg::Prop(_) => ArgKind::Prop,
+        }
+    }
+}
 
-    /// ```notrust
-    ///  phi -> psi    neg psi
-    /// ------------------------
-    ///          neg phi
-    /// ```
-    ModusTollens {
-        implication: StepIndex,
-        negated_rhs: StepIndex,
-    },
+/// An argument of a rule as it is stored. Whether an index cites a step or a proof box is
+/// given by the parameters of the rule in [`RULES`](crate::RULES).
+enum Operand<'a> {
+    Index(StepIndex),
+    Prop(&'a Prop),
+}
 
-    /// ```notrust
-    ///       phi
-    /// ---------------
-    ///  neg (neg phi)
-    /// ```
-    DoubleNegI(StepIndex),
+impl Rule {
+    /// The arguments of the rule, in the same order as they are written
+    pub fn args(&self) -> Vec<RuleArg<'_>> {
+        let params = RuleName::from(self).info().params;
+        params
+            .iter()
+            .zip(self.operands())
+            .map(|(param, operand)| match (param.kind, operand) {
+                (ArgKind::Step, Operand::Index(i)) => RuleArg::Step(i),
+                (ArgKind::ProofBox, Operand::Index(i)) => RuleArg::ProofBox(i),
+                (ArgKind::Prop, Operand::Prop(prop)) => RuleArg::Prop(prop),
+                _ => unreachable!("the arguments of {self:?} match its parameters"),
+            })
+            .collect()
+    }
 
-    /// ```notrust
-    ///  [not phi]...bottom
-    /// --------------------
-    ///         phi
-    /// ```
-    ProofByContradiction(StepIndex),
+    /// Build a rule from its name and arguments, if the arguments match the parameters of the rule
+    pub fn from_args(name: RuleName, args: &[RuleArg<'_>]) -> Option<Rule> {
+        let params = name.info().params;
+        if args.len() != params.len()
+            || params
+                .iter()
+                .zip(args)
+                .any(|(param, arg)| param.kind != arg.kind())
+        {
+            return None;
+        }
 
-    /// ```notrust
-    /// ----------------
-    ///  phi \/ neg phi
-    /// ```
-    LawOfExcludedMiddle(Prop),
+        let mut indices = args.iter().filter_map(|arg| match arg {
+            RuleArg::Step(i) | RuleArg::ProofBox(i) => Some(*i),
+            RuleArg::Prop(_) => None,
+        });
+        let mut props = args.iter().filter_map(|arg| match arg {
+            RuleArg::Prop(prop) => Some((*prop).clone()),
+            _ => None,
+        });
+        Some(Rule::build(
+            name,
+            || indices.next().expect("checked against the parameters"),
+            || props.next().expect("checked against the parameters"),
+        ))
+    }
+
+    /// Change the steps and proof boxes cited by the rule, e.g. after steps have been inserted
+    pub fn renumber(&self, f: impl Fn(StepIndex) -> StepIndex) -> Self {
+        let args = self
+            .args()
+            .into_iter()
+            .map(|arg| match arg {
+                RuleArg::Step(i) => RuleArg::Step(f(i)),
+                RuleArg::ProofBox(i) => RuleArg::ProofBox(f(i)),
+                RuleArg::Prop(prop) => RuleArg::Prop(prop),
+            })
+            .collect::<Vec<_>>();
+        Rule::from_args(self.into(), &args).expect("the arguments have the same kinds as before")
+    }
 }
 
-impl fmt::Display for Rule {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Rule::AndI(phi, psi) => {
-                write!(f, "{name} {phi} {psi}", name = Into::<RuleName>::into(self))
-            }
-            Rule::AndELhs(phi) => write!(f, "{name} {phi}", name = Into::<RuleName>::into(self)),
-            Rule::AndERhs(phi) => write!(f, "{name} {phi}", name = Into::<RuleName>::into(self)),
-            Rule::OrILhs(phi, psi) => {
-                write!(f, "{name} {phi} {psi}", name = Into::<RuleName>::into(self))
-            }
-            Rule::OrIRhs(phi, psi) => {
-                write!(f, "{name} {phi} {psi}", name = Into::<RuleName>::into(self))
-            }
-            Rule::OrE {
-                or_prop,
-                lhs_box,
-                rhs_box,
-            } => write!(
-                f,
-                "{name} {or_prop} {lhs_box} {rhs_box}",
-                name = Into::<RuleName>::into(self)
-            ),
-            Rule::NegI(phi) => write!(f, "{name} {phi}", name = Into::<RuleName>::into(self)),
-            Rule::NegE { prop, neg_prop } => write!(
-                f,
-                "{name} {prop} {neg_prop}",
-                name = Into::<RuleName>::into(self)
-            ),
-            Rule::ImplyI(phi) => write!(f, "{name} {phi}", name = Into::<RuleName>::into(self)),
-            Rule::ImplyE {
-                implication,
-                lhs_proof,
-            } => write!(
-                f,
-                "{name} {implication} {lhs_proof}",
-                name = Into::<RuleName>::into(self)
-            ),
-            Rule::BottomE(phi, psi) => {
-                write!(f, "{name} {phi} {psi}", name = Into::<RuleName>::into(self))
-            }
-            Rule::DoubleNegE(phi) => write!(f, "{name} {phi}", name = Into::<RuleName>::into(self)),
-            Rule::ModusTollens {
-                implication,
-                negated_rhs,
-            } => write!(
-                f,
-                "{name} {implication} {negated_rhs}",
-                name = Into::<RuleName>::into(self)
-            ),
-            Rule::DoubleNegI(phi) => write!(f, "{name} {phi}", name = Into::<RuleName>::into(self)),
-            Rule::ProofByContradiction(phi) => {
-                write!(f, "{name} {phi}", name = Into::<RuleName>::into(self))
-            }
-            Rule::LawOfExcludedMiddle(phi) => {
-                write!(f, "{name} {phi}", name = Into::<RuleName>::into(self))
-            }
+impl Rule {
+    /// Format the rule with its arguments. In ASCII notation the rule is written by its
+    /// canonical name, e.g. `imply_e 1 2` rather than `→e 1 2`.
+    pub fn display(&self, notation: Notation) -> DisplayRule<'_> {
+        DisplayRule {
+            rule: self,
+            notation,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum RuleName {
-    AndI,
-    AndELhs,
-    AndERhs,
-    OrILhs,
-    OrIRhs,
-    OrE,
-    NegI,
-    NegE,
-    ImplyI,
-    ImplyE,
-    BottomE,
-    DoubleNegE,
-    ModusTollens,
-    DoubleNegI,
-    ProofByContradiction,
-    LawOfExcludedMiddle,
+/// See [`Rule::display`]
+pub struct DisplayRule<'a> {
+    rule: &'a Rule,
+    notation: Notation,
 }
 
-impl From<&Rule> for RuleName {
-    fn from(rule: &Rule) -> Self {
-        match rule {
-            Rule::AndI(..) => RuleName::AndI,
-            Rule::AndELhs(..) => RuleName::AndELhs,
-            Rule::AndERhs(..) => RuleName::AndERhs,
-            Rule::OrILhs(..) => RuleName::OrILhs,
-            Rule::OrIRhs(..) => RuleName::OrIRhs,
-            Rule::OrE { .. } => RuleName::OrE,
-            Rule::NegI(..) => RuleName::NegI,
-            Rule::NegE { .. } => RuleName::NegE,
-            Rule::ImplyI(..) => RuleName::ImplyI,
-            Rule::ImplyE { .. } => RuleName::ImplyE,
-            Rule::BottomE(..) => RuleName::BottomE,
-            Rule::DoubleNegE(..) => RuleName::DoubleNegE,
-            Rule::ModusTollens { .. } => RuleName::ModusTollens,
-            Rule::DoubleNegI(..) => RuleName::DoubleNegI,
-            Rule::ProofByContradiction(..) => RuleName::ProofByContradiction,
-            Rule::LawOfExcludedMiddle(..) => RuleName::LawOfExcludedMiddle,
+impl fmt::Display for DisplayRule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let info = RuleName::from(self.rule).info();
+        write!(f, "{}", info.label_in(self.notation))?;
+        for arg in self.rule.args() {
+            match arg {
+                RuleArg::Step(i) | RuleArg::ProofBox(i) => write!(f, " {i}")?,
+                RuleArg::Prop(prop) => write!(f, " {}", prop.display(self.notation))?,
+            }
         }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(Notation::Unicode))
     }
 }
 
 impl fmt::Display for RuleName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use RuleName::*;
+        write!(f, "{}", self.info().label)
+    }
+}
+
+/// The rules that may be used in a proof. Exercises restrict them to make the student
+/// work from first principles, e.g. proving the law of the excluded middle without `lem`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AllowedRules {
+    #[default]
+    All,
+    Only(Vec<RuleName>),
+    AllExcept(Vec<RuleName>),
+}
+
+impl AllowedRules {
+    pub fn allows(&self, rule: RuleName) -> bool {
         match self {
-            AndI => write!(f, "∧I"),
-            AndELhs => write!(f, "∧E_lhs"),
-            AndERhs => write!(f, "∧E_rhs"),
-            OrILhs => write!(f, "∨I_lhs"),
-            OrIRhs => write!(f, "∨I_rhs"),
-            OrE => write!(f, "∨E"),
-            NegI => write!(f, "¬I"),
-            NegE => write!(f, "¬E"),
-            ImplyI => write!(f, "→I"),
-            ImplyE => write!(f, "→E"),
-            BottomE => write!(f, "⊥E"),
-            DoubleNegE => write!(f, "¬¬E"),
-            ModusTollens => write!(f, "MT"),
-            DoubleNegI => write!(f, "¬¬I"),
-            ProofByContradiction => write!(f, "PBC"),
-            LawOfExcludedMiddle => write!(f, "LEM"),
+            AllowedRules::All => true,
+            AllowedRules::Only(rules) => rules.contains(&rule),
+            AllowedRules::AllExcept(rules) => !rules.contains(&rule),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RULES;
+
+    #[test]
+    fn args_follow_the_registry() {
+        let p = Prop::symbol("p");
+        for info in RULES {
+            let args = info
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, param)| match param.kind {
+                    ArgKind::Step => RuleArg::Step(StepIndex(i + 1)),
+                    ArgKind::ProofBox => RuleArg::ProofBox(StepIndex(i + 1)),
+                    ArgKind::Prop => RuleArg::Prop(&p),
+                })
+                .collect::<Vec<_>>();
+            let rule = Rule::from_args(info.name, &args).unwrap();
+            assert_eq!(RuleName::from(&rule), info.name);
+            assert_eq!(rule.args(), args);
+
+            // Too few arguments, or one of the wrong kind
+            assert_eq!(Rule::from_args(info.name, &args[1..]), None);
+            let swapped = args.iter().rev().copied().collect::<Vec<_>>();
+            if swapped.iter().zip(&args).any(|(a, b)| a.kind() != b.kind()) {
+                assert_eq!(Rule::from_args(info.name, &swapped), None);
+            }
         }
     }
 }
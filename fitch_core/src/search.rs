@@ -0,0 +1,159 @@
+use crate::{ArgKind, Error, Proof, Prop, PropVariant, Rule, RuleArg, RULES};
+
+/// How many of the closest rule applications are suggested when no rule fits
+const NEAR_MISSES: usize = 3;
+
+impl Proof {
+    /// Find a single rule application in the current scope that derives exactly the target,
+    /// trying every allowed rule with every combination of accessible steps and closed proof
+    /// boxes. Formulas given as arguments to rules are taken from the target and its
+    /// immediate parts, e.g. the disjuncts of `p ∨ q` for `∨I`.
+    ///
+    /// If no rule fits, the error lists the applications whose formulas come closest, i.e.
+    /// have the same outermost connective as the target and differ the furthest inside it.
+    pub fn find_rule(&self, target: &Prop) -> Result<Rule, Error> {
+        let mut props = vec![target];
+        if let Prop::And(lhs, rhs) | Prop::Or(lhs, rhs) | Prop::Imply(lhs, rhs) = target {
+            props.extend([&**lhs, &**rhs]);
+        }
+
+        let steps = self.accessible_steps();
+        let mut near_misses: Vec<(Rule, Prop, usize)> = vec![];
+        for info in RULES {
+            if !self.allowed_rules.allows(info.name) {
+                continue;
+            }
+
+            let mut combinations = vec![vec![]];
+            for param in info.params {
+                let choices: Vec<_> = match param.kind {
+                    ArgKind::Step => steps
+                        .iter()
+                        .filter(|(_, step)| param.accepts(PropVariant::from(step.prop())))
+                        .map(|(i, _)| RuleArg::Step(*i))
+                        .collect(),
+                    ArgKind::ProofBox => steps
+                        .iter()
+                        .filter(|(_, step)| matches!(step.prop(), Prop::ProofBox(_)))
+                        .map(|(i, _)| RuleArg::ProofBox(*i))
+                        .collect(),
+                    ArgKind::Prop => props.iter().map(|prop| RuleArg::Prop(prop)).collect(),
+                };
+                combinations = extend_all(combinations, &choices);
+            }
+
+            for args in combinations {
+                let Some(rule) = Rule::from_args(info.name, &args) else {
+                    continue;
+                };
+                let Ok(derived) = self.derive(&rule) else {
+                    continue;
+                };
+                if &derived == target {
+                    return Ok(rule);
+                }
+
+                let similar = PropVariant::from(&derived) == PropVariant::from(target);
+                if let Some(path) = derived.diff(target).filter(|_| similar) {
+                    if !near_misses.iter().any(|(_, prop, _)| prop == &derived) {
+                        near_misses.push((rule, derived, path.len()));
+                    }
+                }
+            }
+        }
+
+        // The sort is stable, so rules listed first in the registry are suggested first
+        near_misses.sort_by_key(|(_, _, depth)| std::cmp::Reverse(*depth));
+        Err(Error::NoRuleFound {
+            target: target.clone(),
+            near_misses: near_misses
+                .into_iter()
+                .take(NEAR_MISSES)
+                .map(|(rule, derived, _)| (rule, derived))
+                .collect(),
+        })
+    }
+}
+
+/// Every way of extending one of the argument lists with one of the choices
+fn extend_all<'a>(
+    combinations: Vec<Vec<RuleArg<'a>>>,
+    choices: &[RuleArg<'a>],
+) -> Vec<Vec<RuleArg<'a>>> {
+    combinations
+        .into_iter()
+        .flat_map(|args| {
+            choices.iter().map(move |choice| {
+                let mut args = args.clone();
+                args.push(*choice);
+                args
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AllowedRules, RuleName, StepIndex};
+
+    #[test]
+    fn finding_a_rule() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof.add_premise(Prop::imply(p.clone(), q.clone()));
+        proof.add_premise(p.clone());
+
+        assert_eq!(
+            proof.find_rule(&q).unwrap(),
+            Rule::ImplyE {
+                implication: StepIndex(1),
+                lhs_proof: StepIndex(2)
+            }
+        );
+        assert_eq!(
+            proof.find_rule(&Prop::or(q.clone(), p.clone())).unwrap(),
+            Rule::OrIRhs(q.clone(), StepIndex(2))
+        );
+
+        proof.add_assumption(q.clone());
+        proof.close_scope().unwrap();
+        assert_eq!(
+            proof.find_rule(&Prop::imply(q.clone(), q.clone())).unwrap(),
+            Rule::ImplyI(StepIndex(3))
+        );
+
+        let mut restricted = Proof::with_rules(AllowedRules::AllExcept(vec![RuleName::ImplyE]));
+        restricted.add_premise(Prop::imply(p.clone(), q.clone()));
+        restricted.add_premise(p);
+        assert!(restricted.find_rule(&q).is_err());
+    }
+
+    #[test]
+    fn near_misses() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.add_premise(q.clone());
+
+        let Err(Error::NoRuleFound { near_misses, .. }) =
+            proof.find_rule(&Prop::and(p.clone(), Prop::symbol("r")))
+        else {
+            panic!("no rule derives p ∧ r");
+        };
+        assert_eq!(
+            near_misses,
+            [
+                (
+                    Rule::AndI(StepIndex(1), StepIndex(1)),
+                    Prop::and(p.clone(), p.clone())
+                ),
+                (
+                    Rule::AndI(StepIndex(1), StepIndex(2)),
+                    Prop::and(p.clone(), q.clone())
+                ),
+                (Rule::AndI(StepIndex(2), StepIndex(1)), Prop::and(q, p)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+
+use crate::{
+    proof::check_eq, BoxRange, Error, Proof, Prop, Sequent, Step, StepIndex, StepType, SubProof,
+};
+
+/// A line of a skeleton proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkeletonLine {
+    Formula(Prop),
+    /// A proof box, whose first line is the assumption
+    ProofBox(Vec<SkeletonLine>),
+}
+
+/// A proof where every formula and proof box is given, but the justifications are left out.
+/// The steps are numbered in order from 1, like in a [`Proof`]. Assumptions need no
+/// justification, every other step is justified by [`Skeleton::justify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skeleton {
+    lines: Vec<SkeletonLine>,
+    justifications: HashMap<StepIndex, StepType>,
+    /// The formulas that steps may be justified as premises of, e.g. the premises of the
+    /// sequent that the skeleton proves
+    premises: Vec<Prop>,
+}
+
+/// A line of the skeleton when its proof boxes are flattened
+pub(crate) enum Item<'a> {
+    Step {
+        index: StepIndex,
+        prop: &'a Prop,
+        assumption: bool,
+    },
+    /// The start of a proof box, just before its assumption
+    Open,
+    Close,
+}
+
+impl Skeleton {
+    pub fn new(lines: Vec<SkeletonLine>) -> Result<Self, Error> {
+        fn check(lines: &[SkeletonLine], next: &mut usize) -> Result<(), Error> {
+            for line in lines {
+                match line {
+                    SkeletonLine::Formula(_) => *next += 1,
+                    SkeletonLine::ProofBox(lines) => match lines.first() {
+                        None => return Err(Error::EmptySubProof),
+                        Some(SkeletonLine::ProofBox(_)) => {
+                            return Err(Error::SubProofWithoutAssumption {
+                                index: StepIndex(*next),
+                            })
+                        }
+                        Some(SkeletonLine::Formula(_)) => check(lines, next)?,
+                    },
+                }
+            }
+            Ok(())
+        }
+
+        check(&lines, &mut 1)?;
+        Ok(Self {
+            lines,
+            justifications: HashMap::new(),
+            premises: vec![],
+        })
+    }
+
+    /// Let the premises of the sequent be justified as premises, once the skeleton is known
+    /// to prove the sequent: it must start with the premises, in order, and end with the
+    /// conclusion outside of proof boxes. Without a sequent no step can be a premise.
+    pub fn for_sequent(self, sequent: &Sequent) -> Result<Self, Error> {
+        for (n, premise) in sequent.premises.iter().enumerate() {
+            match self.lines.get(n) {
+                Some(SkeletonLine::Formula(prop)) if prop == premise => (),
+                _ => {
+                    return Err(Error::SkeletonPremise {
+                        index: StepIndex(n + 1),
+                        premise: premise.clone(),
+                    })
+                }
+            }
+        }
+        match self.lines.last() {
+            Some(SkeletonLine::Formula(prop)) if prop == &sequent.conclusion => Ok(Self {
+                premises: sequent.premises.clone(),
+                ..self
+            }),
+            _ => Err(Error::SkeletonConclusion {
+                conclusion: sequent.conclusion.clone(),
+            }),
+        }
+    }
+
+    /// Every step of the skeleton and where its proof boxes start and end, in order
+    pub(crate) fn items(&self) -> Vec<Item<'_>> {
+        fn flatten<'a>(
+            lines: &'a [SkeletonLine],
+            in_box: bool,
+            next: &mut usize,
+            items: &mut Vec<Item<'a>>,
+        ) {
+            for (n, line) in lines.iter().enumerate() {
+                match line {
+                    SkeletonLine::Formula(prop) => {
+                        items.push(Item::Step {
+                            index: StepIndex(*next),
+                            prop,
+                            assumption: in_box && n == 0,
+                        });
+                        *next += 1;
+                    }
+                    SkeletonLine::ProofBox(lines) => {
+                        items.push(Item::Open);
+                        flatten(lines, true, next, items);
+                        items.push(Item::Close);
+                    }
+                }
+            }
+        }
+
+        let mut items = vec![];
+        flatten(&self.lines, false, &mut 1, &mut items);
+        items
+    }
+
+    /// The ranges of all proof boxes, indexed by their first step
+    pub(crate) fn box_ranges(&self) -> HashMap<StepIndex, BoxRange> {
+        let mut ranges = HashMap::new();
+        let mut starts = vec![];
+        let mut last = StepIndex(0);
+        for item in self.items() {
+            match item {
+                Item::Step { index, .. } => {
+                    if starts.last() == Some(&None) {
+                        *starts.last_mut().unwrap() = Some(index);
+                    }
+                    last = index;
+                }
+                Item::Open => starts.push(None),
+                Item::Close => {
+                    let start = starts.pop().flatten().expect("proof boxes are not empty");
+                    ranges.insert(start, BoxRange { start, end: last });
+                }
+            }
+        }
+        ranges
+    }
+
+    pub fn check_box_range(&self, range: &BoxRange) -> Result<(), Error> {
+        match self.box_ranges().get(&range.start) {
+            Some(found) if found == range => Ok(()),
+            _ => Err(Error::InvalidBoxRange { range: *range }),
+        }
+    }
+
+    pub fn justification(&self, index: StepIndex) -> Option<&StepType> {
+        self.justifications.get(&index)
+    }
+
+    /// Justify a step, if the justification derives exactly the formula of the step from
+    /// the steps above it. The formulas of those steps are taken as given, whether they
+    /// have been justified yet or not. Only the steps that the skeleton starts with, whose
+    /// formulas are the premises of the skeleton, can be justified as premises.
+    pub fn justify(&mut self, index: StepIndex, step_type: StepType) -> Result<(), Error> {
+        let (proof, prop, assumption) = self.proof_before(index)?;
+        if assumption {
+            return Err(Error::JustifiedAssumption { index });
+        }
+
+        let derived = match &step_type {
+            StepType::Rule(rule) => proof.derive(rule)?,
+            StepType::Copy(i) => proof.get_prop(*i)?.clone(),
+            StepType::Premise if proof.context.len() > 1 => {
+                return Err(Error::MisplacedPremise { index })
+            }
+            StepType::Premise if !self.premises.contains(prop) => {
+                return Err(Error::NotAPremise { index })
+            }
+            // The skeleton starts with the premises, and the same formula further down must
+            // be derived
+            StepType::Premise if index.0 > self.premises.len() => {
+                return Err(Error::MisplacedPremise { index })
+            }
+            StepType::Premise => prop.clone(),
+            StepType::Assumption => return Err(Error::MisplacedAssumption { index }),
+        };
+        check_eq(prop, &derived)?;
+
+        self.justifications.insert(index, step_type);
+        Ok(())
+    }
+
+    /// The steps that still need a justification
+    pub fn unjustified(&self) -> Vec<StepIndex> {
+        self.items()
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Step {
+                    index,
+                    assumption: false,
+                    ..
+                } if !self.justifications.contains_key(&index) => Some(index),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The proof that the skeleton describes, once every step has been justified
+    pub fn to_proof(&self) -> Option<Proof> {
+        if !self.unjustified().is_empty() {
+            return None;
+        }
+
+        let mut proof = Proof::new();
+        for item in self.items() {
+            match item {
+                Item::Step {
+                    prop,
+                    assumption: true,
+                    ..
+                } => {
+                    proof.add_assumption(prop.clone());
+                }
+                Item::Step { index, prop, .. } => {
+                    let step_type = self.justifications[&index].clone();
+                    proof.add_step(Step::new(prop.clone(), step_type));
+                }
+                Item::Open => (),
+                Item::Close => proof
+                    .close_scope()
+                    .expect("a proof box always opens a new scope"),
+            }
+        }
+        Some(proof)
+    }
+
+    /// The proof up to the given step, with the formula of the step and whether it is an
+    /// assumption. The steps are added as they are stated, regardless of their justifications.
+    fn proof_before(&self, target: StepIndex) -> Result<(Proof, &Prop, bool), Error> {
+        let mut proof = Proof::new();
+        for item in self.items() {
+            match item {
+                Item::Step {
+                    index,
+                    prop,
+                    assumption,
+                } if index == target => return Ok((proof, prop, assumption)),
+                Item::Step {
+                    prop,
+                    assumption: true,
+                    ..
+                } => {
+                    proof.add_assumption(prop.clone());
+                }
+                Item::Step { index, prop, .. } => {
+                    let step_type = self
+                        .justifications
+                        .get(&index)
+                        .cloned()
+                        .unwrap_or(StepType::Premise);
+                    proof.add_step(Step::new(prop.clone(), step_type));
+                }
+                Item::Open => (),
+                Item::Close => proof
+                    .close_scope()
+                    .expect("a proof box always opens a new scope"),
+            }
+        }
+        Err(Error::InvalidStepIndex { index: target })
+    }
+}
+
+/// The skeleton of an existing proof, with its justifications left out
+impl From<&Proof> for Skeleton {
+    fn from(proof: &Proof) -> Self {
+        fn lines(steps: &[(StepIndex, &Step)]) -> Vec<SkeletonLine> {
+            steps
+                .iter()
+                .map(|(_, step)| match step.prop() {
+                    Prop::ProofBox(SubProof(steps)) => {
+                        let steps = steps.iter().map(|(i, s)| (*i, s)).collect::<Vec<_>>();
+                        SkeletonLine::ProofBox(lines(&steps))
+                    }
+                    prop => SkeletonLine::Formula(prop.clone()),
+                })
+                .collect()
+        }
+
+        let premises = proof.context[0]
+            .steps
+            .values()
+            .filter(|step| step.step_type() == &StepType::Premise)
+            .map(|step| step.prop().clone())
+            .collect();
+
+        // Every scope after the global one is a proof box that is still open
+        let mut skeleton = vec![];
+        for scope in proof.context.iter().rev() {
+            let mut steps = scope.steps.iter().map(|(i, s)| (*i, s)).collect::<Vec<_>>();
+            steps.sort_by_key(|(i, _)| *i);
+            let mut scope_lines = lines(&steps);
+            if !skeleton.is_empty() {
+                scope_lines.push(SkeletonLine::ProofBox(skeleton));
+            }
+            skeleton = scope_lines;
+        }
+
+        Self {
+            lines: skeleton,
+            justifications: HashMap::new(),
+            premises,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rule;
+
+    #[test]
+    fn justifying_a_skeleton() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof.add_premise(p.clone());
+        proof.add_assumption(q.clone());
+        proof.copy(StepIndex(1)).unwrap();
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::ImplyI(StepIndex(2))).unwrap();
+
+        let mut skeleton = Skeleton::from(&proof);
+        assert_eq!(skeleton.unjustified(), [1, 3, 4].map(StepIndex));
+        assert_eq!(
+            skeleton.box_ranges()[&StepIndex(2)],
+            BoxRange {
+                start: StepIndex(2),
+                end: StepIndex(3)
+            }
+        );
+
+        // Justifications are checked against the formulas of the steps above, in any order
+        skeleton
+            .justify(StepIndex(4), StepType::Rule(Rule::ImplyI(StepIndex(2))))
+            .unwrap();
+        assert!(matches!(
+            skeleton.justify(StepIndex(3), StepType::Copy(StepIndex(2))),
+            Err(Error::PropMismatch { .. })
+        ));
+        assert!(matches!(
+            skeleton.justify(StepIndex(3), StepType::Premise),
+            Err(Error::MisplacedPremise { .. })
+        ));
+        assert!(matches!(
+            skeleton.justify(StepIndex(2), StepType::Premise),
+            Err(Error::JustifiedAssumption { .. })
+        ));
+        skeleton
+            .justify(StepIndex(3), StepType::Copy(StepIndex(1)))
+            .unwrap();
+        assert!(skeleton.to_proof().is_none());
+
+        skeleton.justify(StepIndex(1), StepType::Premise).unwrap();
+        let solved = skeleton.to_proof().unwrap();
+        assert!(solved.validate().is_ok());
+        assert_eq!(Skeleton::from(&solved).lines, skeleton.lines);
+    }
+
+    #[test]
+    fn only_given_premises_are_premises() {
+        let p = Prop::symbol("p");
+        let p_and_p = Prop::and(p.clone(), p.clone());
+        let sequent = Sequent {
+            premises: vec![p.clone()],
+            conclusion: p_and_p.clone(),
+        };
+        let mut skeleton = Skeleton::new(vec![
+            SkeletonLine::Formula(p.clone()),
+            SkeletonLine::Formula(p_and_p.clone()),
+        ])
+        .unwrap()
+        .for_sequent(&sequent)
+        .unwrap();
+
+        // Neither the conclusion nor any other line that isn't given can be a premise
+        assert!(matches!(
+            skeleton.justify(StepIndex(2), StepType::Premise),
+            Err(Error::NotAPremise { .. })
+        ));
+        skeleton.justify(StepIndex(1), StepType::Premise).unwrap();
+        assert_eq!(skeleton.unjustified(), [StepIndex(2)]);
+
+        // A premise that is stated again after the premises must be derived
+        let mut repeated = Skeleton::new(vec![
+            SkeletonLine::Formula(p.clone()),
+            SkeletonLine::Formula(p.clone()),
+            SkeletonLine::Formula(p_and_p.clone()),
+        ])
+        .unwrap()
+        .for_sequent(&sequent)
+        .unwrap();
+        assert!(matches!(
+            repeated.justify(StepIndex(2), StepType::Premise),
+            Err(Error::MisplacedPremise { .. })
+        ));
+
+        let mut unknown = Skeleton::new(vec![SkeletonLine::Formula(p)]).unwrap();
+        assert!(matches!(
+            unknown.justify(StepIndex(1), StepType::Premise),
+            Err(Error::NotAPremise { .. })
+        ));
+    }
+
+    #[test]
+    fn skeletons_prove_their_sequent() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let skeleton = |lines: &[&Prop]| {
+            let lines = lines
+                .iter()
+                .map(|prop| SkeletonLine::Formula(Prop::clone(prop)));
+            Skeleton::new(lines.collect()).unwrap()
+        };
+        let sequent = Sequent {
+            premises: vec![p.clone(), q.clone()],
+            conclusion: Prop::and(p.clone(), q.clone()),
+        };
+        let p_and_q = &sequent.conclusion;
+
+        assert!(skeleton(&[&p, &q, p_and_q]).for_sequent(&sequent).is_ok());
+        assert!(matches!(
+            skeleton(&[&q, &p, p_and_q]).for_sequent(&sequent),
+            Err(Error::SkeletonPremise {
+                index: StepIndex(1),
+                ..
+            })
+        ));
+        assert!(matches!(
+            skeleton(&[&p, p_and_q]).for_sequent(&sequent),
+            Err(Error::SkeletonPremise {
+                index: StepIndex(2),
+                ..
+            })
+        ));
+        assert!(matches!(
+            skeleton(&[&p, &q, &p]).for_sequent(&sequent),
+            Err(Error::SkeletonConclusion { .. })
+        ));
+    }
+
+    #[test]
+    fn boxes_start_with_an_assumption() {
+        let p = SkeletonLine::Formula(Prop::symbol("p"));
+        assert!(matches!(
+            Skeleton::new(vec![p.clone(), SkeletonLine::ProofBox(vec![])]),
+            Err(Error::EmptySubProof)
+        ));
+        assert!(matches!(
+            Skeleton::new(vec![SkeletonLine::ProofBox(vec![SkeletonLine::ProofBox(
+                vec![p]
+            )])]),
+            Err(Error::SubProofWithoutAssumption { .. })
+        ));
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::{Proof, Prop, RuleName, Step, StepType};
+
+/// Measures of the size and shape of a proof, e.g. to compare a solution with a reference
+/// proof. Proof boxes that are still open are counted like closed ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofStats {
+    /// Every step, including the steps inside of proof boxes
+    pub lines: usize,
+    pub boxes: usize,
+    /// How deeply the proof boxes are nested, zero if there are none
+    pub max_depth: usize,
+    /// How many times each rule is applied. Rules that are not used are left out.
+    pub rules: HashMap<RuleName, usize>,
+    pub copies: usize,
+    /// Applications of the rules that only hold in classical logic
+    pub classical: usize,
+    /// The size of the largest formula, see [`formula_size`]
+    pub max_formula_size: usize,
+    /// The sizes of the formulas of all steps added together
+    pub total_formula_size: usize,
+}
+
+impl ProofStats {
+    pub fn average_formula_size(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.total_formula_size as f64 / self.lines as f64
+        }
+    }
+
+    fn add_step(&mut self, step: &Step, depth: usize) {
+        let size = match step.prop() {
+            Prop::ProofBox(subproof) => {
+                self.add_box(depth + 1);
+                for (_, step) in subproof.steps() {
+                    self.add_step(step, depth + 1);
+                }
+                return;
+            }
+            prop => formula_size(prop),
+        };
+
+        self.lines += 1;
+        self.max_formula_size = self.max_formula_size.max(size);
+        self.total_formula_size += size;
+        match step.step_type() {
+            StepType::Rule(rule) => {
+                let name = RuleName::from(rule);
+                *self.rules.entry(name).or_default() += 1;
+                if name.is_classical() {
+                    self.classical += 1;
+                }
+            }
+            StepType::Copy(_) => self.copies += 1,
+            StepType::Premise | StepType::Assumption => (),
+        }
+    }
+
+    fn add_box(&mut self, depth: usize) {
+        self.boxes += 1;
+        self.max_depth = self.max_depth.max(depth);
+    }
+}
+
+/// The number of symbols, constants and connectives in a formula, where a negation counts
+/// as a single connective even though it is an implication of `⊥`
+pub fn formula_size(prop: &Prop) -> usize {
+    match prop {
+        Prop::Bottom | Prop::Symbol(_) => 1,
+        Prop::Imply(lhs, rhs) if **rhs == Prop::Bottom => 1 + formula_size(lhs),
+        Prop::And(lhs, rhs) | Prop::Or(lhs, rhs) | Prop::Imply(lhs, rhs) => {
+            1 + formula_size(lhs) + formula_size(rhs)
+        }
+        Prop::ProofBox(_) => 0,
+    }
+}
+
+impl Proof {
+    pub fn stats(&self) -> ProofStats {
+        let mut stats = ProofStats::default();
+        // Every scope after the global one is a proof box that is still open
+        for (depth, scope) in self.context.iter().enumerate() {
+            if depth > 0 {
+                stats.add_box(depth);
+            }
+            for step in scope.steps.values() {
+                stats.add_step(step, depth);
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rule, StepIndex};
+
+    #[test]
+    fn stats_of_a_proof() {
+        let (p, q) = (Prop::symbol("p"), Prop::symbol("q"));
+        let mut proof = Proof::new();
+        proof.add_premise(Prop::negated(Prop::negated(p.clone())));
+        proof.apply_rule(&Rule::DoubleNegE(StepIndex(1))).unwrap();
+        proof.add_assumption(q.clone());
+        proof.add_assumption(p.clone());
+        proof.copy(StepIndex(2)).unwrap();
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::ImplyI(StepIndex(4))).unwrap();
+        proof.close_scope().unwrap();
+        proof.apply_rule(&Rule::ImplyI(StepIndex(3))).unwrap();
+        proof.add_assumption(q);
+
+        let stats = proof.stats();
+        assert_eq!(stats.lines, proof.len());
+        assert_eq!((stats.boxes, stats.max_depth), (3, 2));
+        assert_eq!(stats.rules[&RuleName::ImplyI], 2);
+        assert_eq!(stats.rules.get(&RuleName::AndI), None);
+        assert_eq!((stats.copies, stats.classical), (1, 1));
+        // q → p → p
+        assert_eq!(stats.max_formula_size, 5);
+        assert_eq!(formula_size(&Prop::negated(Prop::negated(p))), 3);
+    }
+}
@@ -1,28 +1,60 @@
 use std::fmt;
 
-use ariadne::{Color, Fmt, Label, Report, ReportKind};
+use ariadne::{Color, Fmt, Label, ReportKind};
 use chumsky::{error::SimpleReason, prelude::*, Stream};
-use fitch_core::{Prop, Rule, RuleName, StepIndex};
+use fitch_core::{
+    AllowedRules, ArgKind, BoxRange, Definitions, Error, Exercise, Expected, Notation, Prop, Rule,
+    RuleArg, RuleName, Sequent, Skeleton, SkeletonLine, StepIndex, StepType, RULES,
+};
 
 pub type Span = std::ops::Range<usize>;
-pub use ariadne::Source;
+pub use ariadne::{Report, Source};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Command {
-    Rule(Rule),
+    /// A rule together with the proof boxes that were cited by their full range of steps
+    Rule(Rule, Vec<BoxRange>),
     Copy(StepIndex),
     Premise(Prop),
     Assume(Prop),
+    /// Derive a formula by whichever rule fits, see `Proof::find_rule`
+    Derive(Prop),
     Discharge,
+    /// Insert steps in front of an existing step, see `Proof::insert_before`. The commands
+    /// only add steps, and are separated by `;` when there are several of them.
+    Insert(StepIndex, Vec<Command>),
+    /// Replace a step with the step that the command adds, see `Proof::edit`
+    Edit(StepIndex, Box<Command>),
     Undo,
     Quit,
-    Help,
+    /// General help, or help about a specific rule
+    Help(Option<RuleName>),
     Latex,
-    // TODO: add an Option<String> to get help about a specific rule
+    /// Measures of the size and shape of the proof
+    Stats,
+    /// Move on to the next exercise without solving the current one
+    Skip,
+    /// Justify a step of a skeleton proof by a rule, a copy or as a premise. The proof boxes
+    /// cited by their full range of steps are included, like for `Rule`.
+    Justify(StepIndex, StepType, Vec<BoxRange>),
+    /// Attach a note to a step, or to the whole proof if no step is given. An empty note
+    /// removes the note that was there.
+    Note(Option<StepIndex>, String),
+    /// Define an abbreviation for a formula, see `Proof::define`
+    Define(String, Prop),
     // TODO: Revert, Table
 }
 
 pub fn parse_command(s: &str) -> Result<Command, Vec<Report<'_>>> {
+    parse_command_with(s, &Definitions::default())
+}
+
+/// Parse a command where the abbreviations that have been defined may be used in place of
+/// formulas. Every abbreviation is expanded, so the command only contains whole formulas.
+pub fn parse_command_with<'a>(
+    s: &'a str,
+    definitions: &Definitions,
+) -> Result<Command, Vec<Report<'a>>> {
     let tokens = lexer().parse(s).map_err(|errors| {
         errors
             .into_iter()
@@ -42,7 +74,59 @@ pub fn parse_command(s: &str) -> Result<Command, Vec<Report<'_>>> {
                 .collect::<Vec<_>>()
         })?;
 
-    Ok(command)
+    Ok(expand(command, definitions))
+}
+
+fn expand(command: Command, definitions: &Definitions) -> Command {
+    match command {
+        Command::Rule(rule, ranges) => Command::Rule(definitions.expand_rule(&rule), ranges),
+        Command::Premise(prop) => Command::Premise(definitions.expand(&prop)),
+        Command::Assume(prop) => Command::Assume(definitions.expand(&prop)),
+        Command::Derive(prop) => Command::Derive(definitions.expand(&prop)),
+        Command::Insert(index, commands) => Command::Insert(
+            index,
+            commands
+                .into_iter()
+                .map(|command| expand(command, definitions))
+                .collect(),
+        ),
+        Command::Edit(index, command) => {
+            Command::Edit(index, Box::new(expand(*command, definitions)))
+        }
+        Command::Justify(index, StepType::Rule(rule), ranges) => Command::Justify(
+            index,
+            StepType::Rule(definitions.expand_rule(&rule)),
+            ranges,
+        ),
+        Command::Define(name, prop) => Command::Define(name, definitions.expand(&prop)),
+        command => command,
+    }
+}
+
+/// Parse a problem set: every exercise is a sequent, optionally followed by the only rules that
+/// may be used (or the rules that may not be used) and the largest number of steps that the
+/// proof may have. A skeleton can be given for exercises where the student only justifies the
+/// steps, with the steps separated by `;` and proof boxes written within `[` and `]`.
+/// Comments start with `#`.
+/// ```notrust
+/// # Week 1
+/// p & q |- q & p
+///     rules = &i &e_lhs &e_rhs
+///     steps = 5
+/// p -> q, q -> r ⊢ p -> r
+/// |- p | -p
+///     without = lem
+/// p -> q |- -q -> -p
+///     skeleton = p -> q; [-q; [p; q; _|_]; -p]; -q -> -p
+/// ```
+pub fn parse_problem_set(s: &str) -> Result<Vec<Exercise>, Vec<Report<'_>>> {
+    problem_set().parse(s).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|error| error.map(|c| c.to_string()))
+            .map(generate_report)
+            .collect::<Vec<_>>()
+    })
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -56,7 +140,22 @@ enum Token {
     Quit,
     Help,
     Latex,
+    Skip,
+    Justify,
+    Derive,
+    Stats,
+    Insert,
+    Before,
+    Edit,
+    Semicolon,
+    Note,
+    Define,
+    /// `:=`
+    Assign,
+    /// Text within double quotes, without the quotes
+    Text(String),
     Index(StepIndex),
+    Range(BoxRange),
     Prop(Prop),
     RuleName(RuleName),
 }
@@ -72,57 +171,152 @@ impl fmt::Display for Token {
             Token::Quit => write!(f, "quit"),
             Token::Help => write!(f, "help"),
             Token::Index(i) => write!(f, "{i}"),
+            Token::Range(range) => write!(f, "{range}"),
             Token::Prop(prop) => write!(f, "{prop}"),
             Token::RuleName(name) => write!(f, "{name}"),
             Token::Latex => write!(f, "latex"),
             Token::Undo => write!(f, "undo"),
+            Token::Skip => write!(f, "skip"),
+            Token::Justify => write!(f, "justify"),
+            Token::Derive => write!(f, "derive"),
+            Token::Stats => write!(f, "stats"),
+            Token::Insert => write!(f, "insert"),
+            Token::Before => write!(f, "before"),
+            Token::Edit => write!(f, "edit"),
+            Token::Semicolon => write!(f, ";"),
+            Token::Note => write!(f, "note"),
+            Token::Define => write!(f, "define"),
+            Token::Assign => write!(f, ":="),
+            Token::Text(text) => write!(f, "\"{text}\""),
         }
     }
 }
 
 fn lexer() -> impl Parser<char, Vec<(Token, Span)>, Error = Simple<char>> {
+    token()
+        .map_with_span(|token, span| (token, span))
+        .padded()
+        .repeated()
+        // TODO: Not totally sure this is a good idea, the nano_rust example from chumsky does not do this
+        .then_ignore(end())
+        .collect()
+}
+
+fn token() -> impl Parser<char, Token, Error = Simple<char>> {
     choice((
-        just("rule").map(|_| Token::Rule),
-        just("undo").map(|_| Token::Undo),
-        just("latex").map(|_| Token::Latex),
-        just("copy").map(|_| Token::Copy),
-        just("premise").map(|_| Token::Premise),
-        just("assume").map(|_| Token::Assume),
-        just("discharge").map(|_| Token::Discharge),
-        just("quit").map(|_| Token::Quit),
-        just("help").map(|_| Token::Help),
+        text::keyword("rule").map(|_| Token::Rule),
+        text::keyword("undo").map(|_| Token::Undo),
+        text::keyword("latex").map(|_| Token::Latex),
+        text::keyword("copy").map(|_| Token::Copy),
+        text::keyword("premise").map(|_| Token::Premise),
+        text::keyword("assume").map(|_| Token::Assume),
+        text::keyword("discharge").map(|_| Token::Discharge),
+        text::keyword("quit").map(|_| Token::Quit),
+        text::keyword("help").map(|_| Token::Help),
+        text::keyword("skip").map(|_| Token::Skip),
+        text::keyword("justify").map(|_| Token::Justify),
+        text::keyword("derive").map(|_| Token::Derive),
+        text::keyword("stats").map(|_| Token::Stats),
+        text::keyword("insert").map(|_| Token::Insert),
+        text::keyword("before").map(|_| Token::Before),
+        text::keyword("edit").map(|_| Token::Edit),
+        just(';').map(|_| Token::Semicolon),
+        text::keyword("note").map(|_| Token::Note),
+        text::keyword("define").map(|_| Token::Define),
+        just(":=").map(|_| Token::Assign),
+        text_literal().map(Token::Text),
         rule_name().map(Token::RuleName),
+        range().map(Token::Range),
         index().map(Token::Index),
         prop().map(Token::Prop),
     ))
-    .map_with_span(|token, span| (token, span))
-    .padded()
-    .repeated()
-    // TODO: Not totally sure this is a good idea, the nano_rust example from chumsky does not do this
-    .then_ignore(end())
-    .collect()
 }
 
-fn command() -> impl Parser<Token, Command, Error = Simple<Token>> {
+fn text_literal() -> impl Parser<char, String, Error = Simple<char>> {
+    filter(|c| *c != '"')
+        .repeated()
+        .delimited_by(just('"'), just('"'))
+        .collect()
+        .labelled("text")
+}
+
+/// The commands that add a step to the proof, or close a proof box
+// The `select!` parsers return `Simple<Token>` errors, which are large because tokens may
+// hold whole propositions
+#[allow(clippy::result_large_err)]
+fn step_command() -> impl Parser<Token, Command, Error = Simple<Token>> {
     let prop = select! {Token::Prop(prop) => prop};
     let index = select! {Token::Index(i) => i};
 
     let copy = just(Token::Copy).ignore_then(index).map(Command::Copy);
     let premise = just(Token::Premise).ignore_then(prop).map(Command::Premise);
     let assume = just(Token::Assume).ignore_then(prop).map(Command::Assume);
-    let rule = just(Token::Rule).ignore_then(rule()).map(Command::Rule);
+    let derive = just(Token::Derive).ignore_then(prop).map(Command::Derive);
+    let rule = just(Token::Rule)
+        .ignore_then(rule())
+        .map(|(rule, ranges)| Command::Rule(rule, ranges));
+    let discharge = just(Token::Discharge).to(Command::Discharge);
+
+    choice((copy, premise, assume, derive, rule, discharge))
+}
+
+#[allow(clippy::result_large_err)]
+fn command() -> impl Parser<Token, Command, Error = Simple<Token>> {
+    let index = select! {Token::Index(i) => i};
+
+    let help = just(Token::Help)
+        .ignore_then(select! {Token::RuleName(name) => name}.or_not())
+        .map(Command::Help);
+    let justification = choice((
+        just(Token::Rule)
+            .ignore_then(rule())
+            .map(|(rule, ranges)| (StepType::Rule(rule), ranges)),
+        just(Token::Copy)
+            .ignore_then(index)
+            .map(|i| (StepType::Copy(i), vec![])),
+        just(Token::Premise).to((StepType::Premise, vec![])),
+    ));
+    let justify = just(Token::Justify)
+        .ignore_then(index)
+        .then(justification)
+        .map(|(index, (step_type, ranges))| Command::Justify(index, step_type, ranges));
+    let insert = just(Token::Insert)
+        .ignore_then(just(Token::Before))
+        .ignore_then(index)
+        .then(
+            step_command()
+                .separated_by(just(Token::Semicolon))
+                .at_least(1),
+        )
+        .map(|(index, commands)| Command::Insert(index, commands));
+    let edit = just(Token::Edit)
+        .ignore_then(index)
+        .then(step_command())
+        .map(|(index, command)| Command::Edit(index, Box::new(command)));
+    let note = just(Token::Note)
+        .ignore_then(index.or_not())
+        .then(select! {Token::Text(text) => text})
+        .map(|(index, text)| Command::Note(index, text));
+    let define = just(Token::Define)
+        .ignore_then(select! {Token::Prop(Prop::Symbol(name)) => name})
+        .then_ignore(just(Token::Assign))
+        .then(select! {Token::Prop(prop) => prop})
+        .map(|(name, prop)| Command::Define(name, prop));
 
     choice((
-        copy,
-        premise,
-        assume,
-        rule,
+        step_command(),
+        insert,
+        edit,
+        justify,
+        note,
+        define,
+        help,
         select! {
-            Token::Discharge => Command::Discharge,
             Token::Undo => Command::Undo,
             Token::Quit => Command::Quit,
-            Token::Help => Command::Help,
             Token::Latex => Command::Latex,
+            Token::Stats => Command::Stats,
+            Token::Skip => Command::Skip,
         },
     ))
     .labelled("command")
@@ -136,14 +330,43 @@ fn index() -> impl Parser<char, StepIndex, Error = Simple<char>> {
         .labelled("index")
 }
 
+/// A proof box cited by its first and last step, e.g. `3-7`
+fn range() -> impl Parser<char, BoxRange, Error = Simple<char>> {
+    index()
+        .then_ignore(just('-').or(just('–')))
+        .then(index())
+        .map(|(start, end)| BoxRange { start, end })
+        .labelled("range")
+}
+
 fn prop() -> impl Parser<char, Prop, Error = Simple<char>> {
-    recursive(|prop| {
-        let bottom = just("bottom").or(just("⊥")).map(|_| Prop::Bottom);
+    prop_with(false)
+}
+
+/// A formula of a sequent, in which `|-` is the turnstile (also at the start of the next
+/// exercise). Elsewhere it is a disjunction with a negation, e.g. `p|-q` is `p ∨ ¬q`.
+fn sequent_prop() -> impl Parser<char, Prop, Error = Simple<char>> {
+    prop_with(true)
+}
+
+fn prop_with(in_sequent: bool) -> impl Parser<char, Prop, Error = Simple<char>> {
+    recursive(move |prop| {
+        let bottom = text::keyword("bottom")
+            .to("bottom")
+            .or(just("⊥"))
+            .or(just("_|_"))
+            .map(|_| Prop::Bottom);
         let symbol = text::ident().map(Prop::Symbol);
 
+        // There is no turnstile inside parentheses
+        let nested = if in_sequent {
+            prop_with(false).boxed()
+        } else {
+            prop.boxed()
+        };
         let atom = bottom
             .or(symbol)
-            .or(prop.delimited_by(just('('), just(')')));
+            .or(nested.delimited_by(just('('), just(')')));
         // TODO: Maybe use chumsky::recovery::nested_delimiters
 
         let negate_op = just('-').or(just('¬')).padded();
@@ -151,193 +374,385 @@ fn prop() -> impl Parser<char, Prop, Error = Simple<char>> {
             .repeated()
             .then(atom)
             // Note negation is implemented as -phi = phi -> bottom
-            .foldr(|_op, rhs| Prop::Imply(Box::new(rhs), Box::new(Prop::Bottom)));
+            .foldr(|_op, rhs| Prop::negated(rhs));
 
         let and_op = just('*').or(just('∧')).or(just('&')).or(just('^')).padded();
         let and = negate
             .clone()
             .then(and_op.ignore_then(negate).repeated())
-            .foldl(|lhs, rhs| Prop::And(Box::new(lhs), Box::new(rhs)));
+            .foldl(Prop::and);
 
-        let or_op = just('+').or(just('∨')).or(just('|').or(just('v'))).padded();
+        let bar = just('|')
+            .then(just('-').rewind().or_not())
+            .try_map(move |(bar, dash), span| match dash {
+                Some(_) if in_sequent => Err(Simple::custom(span, "the turnstile")),
+                _ => Ok(bar),
+            });
+        let or_op = just('+').or(just('∨')).or(bar).or(just('v')).padded();
         let or = and
             .clone()
             .then(or_op.ignore_then(and).repeated())
-            .foldl(|lhs, rhs| Prop::Or(Box::new(lhs), Box::new(rhs)));
+            .foldl(Prop::or);
 
         let implies_op = just("->").or(just("⇒")).or(just("→")).padded();
-        let implies = or
-            .clone()
+        or.clone()
             .then_ignore(implies_op)
             .repeated()
             .then(or)
-            .foldr(|lhs, rhs| Prop::Imply(Box::new(lhs), Box::new(rhs)));
-
-        implies
+            .foldr(Prop::imply)
     })
 }
 
-fn rule_name() -> impl Parser<char, RuleName, Error = Simple<char>> {
-    let and_prefix = just("and_").or(just("∧")).or(just("&")).or(just("^"));
-    let or_prefix = just("or_").or(just("∨")).or(just("|")).or(just("v"));
-    let neg_prefix = just("neg_").or(just("-")).or(just("¬"));
-    let imply_prefix = just("imply_").or(just("->")).or(just("⇒")).or(just("→"));
-    let double_neg_prefix = just("neg_neg_").or(just("--")).or(just("¬¬"));
-
-    let and_i = and_prefix.then_ignore(just("i")).to(RuleName::AndI);
-    let and_e_lhs = and_prefix.then_ignore(just("e_lhs")).to(RuleName::AndELhs);
-    let and_e_rhs = and_prefix.then_ignore(just("e_rhs")).to(RuleName::AndERhs);
-    let or_i_lhs = or_prefix.then_ignore(just("i_lhs")).to(RuleName::OrILhs);
-    let or_i_rhs = or_prefix.then_ignore(just("i_rhs")).to(RuleName::OrIRhs);
-    let or_e = or_prefix.then_ignore(just("e")).to(RuleName::OrE);
-    let neg_i = neg_prefix.then_ignore(just("i")).to(RuleName::NegI);
-    let neg_e = neg_prefix.then_ignore(just("e")).to(RuleName::NegE);
-    let imply_i = imply_prefix.then_ignore(just("i")).to(RuleName::ImplyI);
-    let imply_e = imply_prefix.then_ignore(just("e")).to(RuleName::ImplyE);
-    let bottom_e = just("bottom_e").to(RuleName::BottomE);
-    let double_neg_e = double_neg_prefix
-        .then_ignore(just("e"))
-        .to(RuleName::DoubleNegE);
-    let double_neg_i = double_neg_prefix
-        .then_ignore(just("i"))
-        .to(RuleName::DoubleNegI);
-    let modus_tollens = just("modus_tollens")
-        .or(just("mt"))
-        .to(RuleName::ModusTollens);
-    let proof_by_contradiction = just("pbc")
-        .or(just("proof_by_contradiction"))
-        .to(RuleName::ProofByContradiction);
-    let law_of_excluded_middle = just("lem")
-        .or(just("law_of_excluded_middle"))
-        .to(RuleName::LawOfExcludedMiddle);
+/// Premises separated by commas, a turnstile and a conclusion, e.g. `p, p -> q |- q`
+fn sequent() -> impl Parser<char, Sequent, Error = Simple<char>> {
+    let inline = filter(|c: &char| c.is_whitespace() && *c != '\n').repeated();
+    sequent_prop()
+        .padded_by(inline)
+        .separated_by(just(','))
+        .then_ignore(just("|-").or(just("⊢")).padded_by(inline))
+        .then(sequent_prop().padded_by(inline))
+        .map(|(premises, conclusion)| Sequent {
+            premises,
+            conclusion,
+        })
+        .labelled("sequent")
+}
 
-    choice((
-        and_i,
-        and_e_lhs,
-        and_e_rhs,
-        or_i_lhs,
-        or_i_rhs,
-        or_e,
-        neg_i,
-        neg_e,
-        imply_i,
-        imply_e,
-        bottom_e,
-        double_neg_e,
-        double_neg_i,
-        modus_tollens,
-        proof_by_contradiction,
-        law_of_excluded_middle,
-    ))
-    .labelled("rule name")
+enum ExerciseOption {
+    Rules(AllowedRules),
+    Steps(usize),
+    Skeleton(Skeleton),
 }
 
-fn rule() -> impl Parser<Token, Rule, Error = Simple<Token>> {
-    let index = select! { Token::Index(i) => i};
-    let prop = select! { Token::Prop(p) => p};
+fn problem_set() -> impl Parser<char, Vec<Exercise>, Error = Simple<char>> {
+    let inline = filter(|c: &char| c.is_whitespace() && *c != '\n').repeated();
+    let comment = just('#').then(filter(|c| *c != '\n').repeated()).ignored();
+    let space = filter(|c: &char| c.is_whitespace())
+        .ignored()
+        .or(comment)
+        .repeated();
+    let setting = |key| text::keyword(key).then(just('=').padded_by(inline));
 
-    let and_i = select! {Token::RuleName(RuleName::AndI) => ()}
-        .ignore_then(index)
-        .then(index)
-        .map(|(lhs, rhs)| Rule::AndI(lhs, rhs));
+    let rule_names = || rule_name().padded_by(inline).repeated().at_least(1);
+    let rules = setting("rules")
+        .ignore_then(rule_names())
+        .map(AllowedRules::Only)
+        .or(setting("without")
+            .ignore_then(rule_names())
+            .map(AllowedRules::AllExcept))
+        .map(ExerciseOption::Rules);
+    let steps = setting("steps")
+        .ignore_then(text::int(10).padded_by(inline))
+        .try_map(|n: String, span| {
+            n.parse()
+                .map(ExerciseOption::Steps)
+                .map_err(|_| Simple::custom(span, "The number of steps is too large"))
+        });
 
-    let and_e_lhs = select! {Token::RuleName(RuleName::AndELhs) => ()}
-        .ignore_then(index)
-        .map(Rule::AndELhs);
+    let skeleton = setting("skeleton")
+        .ignore_then(recursive(|lines| {
+            let open = just('[').padded_by(inline);
+            let close = just(']').padded_by(inline);
+            prop()
+                .padded_by(inline)
+                .map(SkeletonLine::Formula)
+                .or(lines.delimited_by(open, close).map(SkeletonLine::ProofBox))
+                .separated_by(just(';'))
+                .at_least(1)
+        }))
+        .try_map(|lines, span| {
+            Skeleton::new(lines).map_err(|error| Simple::custom(span, error.to_string()))
+        })
+        .map(ExerciseOption::Skeleton);
 
-    let and_e_rhs = select! {Token::RuleName(RuleName::AndERhs) => ()}
-        .ignore_then(index)
-        .map(Rule::AndERhs);
+    let exercise = sequent()
+        .then(rules.or(steps).or(skeleton).padded_by(space).repeated())
+        .try_map(|(sequent, options), span| {
+            let mut exercise = Exercise::new(sequent);
+            for option in options {
+                match option {
+                    ExerciseOption::Rules(rules) => exercise.rules = rules,
+                    ExerciseOption::Steps(steps) => exercise.max_steps = Some(steps),
+                    ExerciseOption::Skeleton(skeleton) => {
+                        let skeleton = skeleton
+                            .for_sequent(&exercise.sequent)
+                            .map_err(|error| Simple::custom(span.clone(), error.to_string()))?;
+                        exercise.skeleton = Some(skeleton);
+                    }
+                }
+            }
+            Ok(exercise)
+        });
 
-    let or_i_lhs = select! {Token::RuleName(RuleName::OrILhs) => ()}
-        .ignore_then(index)
-        .then(prop)
-        .map(|(lhs, rhs)| Rule::OrILhs(lhs, rhs));
+    space
+        .ignore_then(exercise.then_ignore(space).repeated())
+        .then_ignore(end())
+}
 
-    let or_i_rhs = select! {Token::RuleName(RuleName::OrIRhs) => ()}
-        .ignore_then(prop)
-        .then(index)
-        .map(|(lhs, rhs)| Rule::OrIRhs(lhs, rhs));
+/// Every alias of every rule in the registry. Longer aliases are tried first so that
+/// e.g. `--e` is not read as `-e` followed by garbage.
+fn rule_name() -> impl Parser<char, RuleName, Error = Simple<char>> {
+    let mut aliases = RULES
+        .iter()
+        .flat_map(|info| info.aliases.iter().map(move |alias| (*alias, info.name)))
+        .collect::<Vec<_>>();
+    aliases.sort_by_key(|(alias, _)| std::cmp::Reverse(alias.chars().count()));
 
-    let or_e = select! {Token::RuleName(RuleName::OrE) => ()}
-        .ignore_then(index)
-        .then(index)
-        .then(index)
-        .map(|((or_prop, lhs_box), rhs_box)| Rule::OrE {
-            or_prop,
-            lhs_box,
-            rhs_box,
-        });
+    // A rule name may not run into a symbol, e.g. `-ex` negates the symbol `ex`
+    let boundary = filter(|c: &char| !c.is_alphanumeric() && *c != '_')
+        .ignored()
+        .or(end())
+        .rewind();
+    choice(
+        aliases
+            .into_iter()
+            .map(|(alias, name)| just(alias).to(name))
+            .collect::<Vec<_>>(),
+    )
+    .then_ignore(boundary)
+    .labelled("rule name")
+}
 
-    let neg_i = select! {Token::RuleName(RuleName::NegI) => ()}
-        .ignore_then(index)
-        .map(Rule::NegI);
+/// Parses a rule and its arguments, checking them against the parameters in the registry.
+/// Proof boxes can either be cited by their first step or by their full range, the ranges
+/// are returned so that they can be checked against the proof.
+#[allow(clippy::result_large_err)]
+fn rule() -> impl Parser<Token, (Rule, Vec<BoxRange>), Error = Simple<Token>> {
+    let name = select! { Token::RuleName(name) => name };
+    let arg = select! {
+        token @ Token::Index(_) => token,
+        token @ Token::Range(_) => token,
+        token @ Token::Prop(_) => token,
+    };
 
-    let neg_e = select! {Token::RuleName(RuleName::NegE) => ()}
-        .ignore_then(index)
-        .then(index)
-        .map(|(prop, neg_prop)| Rule::NegE { prop, neg_prop });
+    name.then(arg.repeated())
+        .try_map(|(name, tokens), span| {
+            let info = name.info();
+            let mut ranges = vec![];
+            let args = info
+                .params
+                .iter()
+                .zip(&tokens)
+                .map(|(param, token)| match (param.kind, token) {
+                    (ArgKind::Step, Token::Index(i)) => Some(RuleArg::Step(*i)),
+                    (ArgKind::ProofBox, Token::Index(i)) => Some(RuleArg::ProofBox(*i)),
+                    (ArgKind::ProofBox, Token::Range(range)) => {
+                        ranges.push(*range);
+                        Some(RuleArg::ProofBox(range.start))
+                    }
+                    (ArgKind::Prop, Token::Prop(prop)) => Some(RuleArg::Prop(prop)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
 
-    let imply_i = select! {Token::RuleName(RuleName::ImplyI) => ()}
-        .ignore_then(index)
-        .map(Rule::ImplyI);
+            match args.and_then(|args| Rule::from_args(name, &args)) {
+                Some(rule) if tokens.len() == info.params.len() => Ok((rule, ranges)),
+                _ => Err(Simple::custom(
+                    span,
+                    format!("{name} is written as '{}'", info.usage()),
+                )),
+            }
+        })
+        .labelled("rule")
+}
 
-    let imply_e = select! {Token::RuleName(RuleName::ImplyE) => ()}
-        .ignore_then(index)
-        .then(index)
-        .map(|(implication, lhs_proof)| Rule::ImplyE {
-            implication,
-            lhs_proof,
-        });
+/// How a part of a command is highlighted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    Keyword,
+    RuleName,
+    /// A step index or a range of steps
+    Index,
+    Symbol,
+    Bottom,
+    /// Connectives and parentheses
+    Operator,
+    /// Text within double quotes, e.g. a note
+    Text,
+    /// Text that isn't a token, or a token that the command doesn't expect
+    Invalid,
+}
 
-    let bottom_e = select! {Token::RuleName(RuleName::BottomE) => ()}
-        .ignore_then(index)
-        .then(prop)
-        .map(|(bottom, prop)| Rule::BottomE(bottom, prop));
+/// Split a command, which may not be finished yet, into highlighted parts. The spans count
+/// characters rather than bytes and the whitespace between the parts is left out.
+pub fn highlight(line: &str) -> Vec<(Span, Highlight)> {
+    let (tokens, rest) = token()
+        .map_with_span(|token, span| (token, span))
+        .padded()
+        .repeated()
+        .then(
+            any()
+                .repeated()
+                .at_least(1)
+                .map_with_span(|_, span: Span| span)
+                .or_not(),
+        )
+        .parse(line)
+        .unwrap_or_default();
 
-    let double_neg_e = select! {Token::RuleName(RuleName::DoubleNegE) => ()}
-        .ignore_then(index)
-        .map(Rule::DoubleNegE);
+    // Only complain about tokens that are out of place, an unfinished command is fine
+    let len = line.chars().count();
+    let unexpected = command()
+        .parse(Stream::from_iter(len..len + 1, tokens.clone().into_iter()))
+        .err()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|error| error.found().is_some() && error.reason() == &SimpleReason::Unexpected)
+        .map(|error| error.span())
+        .collect::<Vec<_>>();
 
-    let double_neg_i = select! {Token::RuleName(RuleName::DoubleNegI) => ()}
-        .ignore_then(index)
-        .map(Rule::DoubleNegI);
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut highlights = vec![];
+    for (token, span) in tokens {
+        if unexpected.contains(&span) {
+            highlights.push((span, Highlight::Invalid));
+            continue;
+        }
+        match token {
+            Token::Prop(_) => highlights.extend(prop_highlights(&chars[span.clone()], span.start)),
+            Token::Index(_) | Token::Range(_) => highlights.push((span, Highlight::Index)),
+            Token::RuleName(_) => highlights.push((span, Highlight::RuleName)),
+            Token::Text(_) => highlights.push((span, Highlight::Text)),
+            _ => highlights.push((span, Highlight::Keyword)),
+        }
+    }
+    if let Some(span) = rest {
+        // A formula with unclosed parentheses is probably just not finished yet
+        let unfinished = lexer()
+            .parse(line)
+            .err()
+            .is_some_and(|errors| errors.iter().all(|error| error.found().is_none()));
+        if chars[span.start] == '"' {
+            // A note that is still being written
+            highlights.push((span, Highlight::Text));
+        } else if unfinished {
+            highlights.extend(prop_highlights(&chars[span.clone()], span.start));
+        } else {
+            highlights.push((span, Highlight::Invalid));
+        }
+    }
+    highlights
+}
 
-    let modus_tollens = select! {Token::RuleName(RuleName::ModusTollens) => ()}
-        .ignore_then(index)
-        .then(index)
-        .map(|(implication, negated_rhs)| Rule::ModusTollens {
-            implication,
-            negated_rhs,
-        });
+/// Highlight the parts of a formula, which starts at character `offset` of the command
+fn prop_highlights(chars: &[char], offset: usize) -> Vec<(Span, Highlight)> {
+    let mut highlights = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let (len, highlight) = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '-' if chars.get(i + 1) == Some(&'>') => (2, Highlight::Operator),
+            '-' | '¬' | '*' | '∧' | '&' | '^' | '+' | '∨' | '|' | '⇒' | '→' | '(' | ')' => {
+                (1, Highlight::Operator)
+            }
+            '⊥' => (1, Highlight::Bottom),
+            '_' if chars[i..].starts_with(&['_', '|', '_']) => (3, Highlight::Bottom),
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '_')
+                    .count()
+                    .max(1);
+                let highlight = match chars[i..i + len].iter().collect::<String>().as_str() {
+                    "bottom" => Highlight::Bottom,
+                    "v" => Highlight::Operator,
+                    _ => Highlight::Symbol,
+                };
+                (len, highlight)
+            }
+        };
+        highlights.push((offset + i..offset + i + len, highlight));
+        i += len;
+    }
+    highlights
+}
 
-    let proof_by_contradiction = select! {Token::RuleName(RuleName::ProofByContradiction) => ()}
-        .ignore_then(index)
-        .map(Rule::ProofByContradiction);
+/// Create a report for an error that occurred when running a command that was parsed
+/// from `line`, pointing out the argument of the command that caused it (if any).
+/// When steps are inserted, `command` is the position of the inserted command that failed,
+/// counting from zero. Propositions in the message are printed in `notation`.
+pub fn report_error(
+    line: &str,
+    command: usize,
+    error: &Error,
+    notation: Notation,
+) -> Report<'static> {
+    let span = error_span(line, command, error).unwrap_or(0..line.chars().count());
+    Report::build(ReportKind::Error, (), span.start)
+        .with_message(error.message_with_highlight(notation, &|s| s.fg(Color::Red).to_string()))
+        .with_label(
+            Label::new(span)
+                .with_message(label_message(error, notation))
+                .with_color(Color::Red),
+        )
+        .finish()
+}
 
-    let law_of_excluded_middle = select! {Token::RuleName(RuleName::LawOfExcludedMiddle) => ()}
-        .ignore_then(prop)
-        .map(Rule::LawOfExcludedMiddle);
+/// The span of the argument that caused the error, see [`report_error`]
+fn error_span(line: &str, command: usize, error: &Error) -> Option<Span> {
+    let tokens = lexer().parse(line).unwrap_or_default();
+    // The step being justified, edited or inserted before is not an argument of the command
+    // after it
+    let tokens = match tokens.as_slice() {
+        [(Token::Justify | Token::Edit | Token::Note, _), _, rest @ ..] => rest,
+        [(Token::Insert, _), _, _, rest @ ..] => rest
+            .split(|(token, _)| token == &Token::Semicolon)
+            .nth(command)
+            .unwrap_or_default(),
+        tokens => tokens,
+    };
 
-    choice((
-        and_i,
-        and_e_lhs,
-        and_e_rhs,
-        or_i_lhs,
-        or_i_rhs,
-        or_e,
-        neg_i,
-        neg_e,
-        imply_i,
-        imply_e,
-        bottom_e,
-        double_neg_e,
-        double_neg_i,
-        modus_tollens,
-        proof_by_contradiction,
-        law_of_excluded_middle,
-    ))
-    .labelled("rule")
+    // Everything after the command keyword (and rule name) is an argument
+    let args = tokens
+        .iter()
+        .skip_while(|(token, _)| is_keyword(token))
+        .collect::<Vec<_>>();
+
+    let span = match error {
+        Error::InvalidArgument { position, .. } => args.get(*position).map(|(_, span)| span),
+        Error::InvalidStepIndex { index } => args
+            .iter()
+            .find(|(token, _)| match token {
+                Token::Index(i) => i == index,
+                Token::Range(range) => range.start == *index,
+                _ => false,
+            })
+            .map(|(_, span)| span),
+        Error::InvalidBoxRange { range } => args
+            .iter()
+            .find(|(token, _)| token == &Token::Range(*range))
+            .map(|(_, span)| span),
+        Error::NoRuleFound { .. }
+        | Error::AlreadyDefined { .. }
+        | Error::RecursiveDefinition { .. } => args.first().map(|(_, span)| span),
+        _ => None,
+    };
+    span.cloned()
+}
+
+/// Command keywords and rule names, i.e. everything that isn't an argument
+fn is_keyword(token: &Token) -> bool {
+    !matches!(token, Token::Index(_) | Token::Range(_) | Token::Prop(_))
+}
+
+fn label_message(error: &Error, notation: Notation) -> String {
+    match error {
+        Error::InvalidArgument {
+            expected: Expected::Prop(prop),
+            ..
+        } => format!("Expected '{}'", prop.display(notation).fg(Color::Yellow)),
+        Error::InvalidArgument { expected, .. } => {
+            format!("Expected {}", expected.describe(notation).fg(Color::Yellow))
+        }
+        Error::InvalidStepIndex { .. } => "This step can't be used here".to_string(),
+        Error::InvalidBoxRange { .. } => "This is not a closed proof box".to_string(),
+        Error::NoRuleFound { .. } => "No rule derives this formula".to_string(),
+        Error::AlreadyDefined { .. } => "This name is already in use".to_string(),
+        error => error.message(notation),
+    }
 }
 
 fn generate_report(error: Simple<String>) -> Report<'static> {
@@ -407,3 +822,382 @@ fn generate_report(error: Simple<String>) -> Report<'static> {
     };
     report.finish()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fitch_core::{Notation, Proof};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn parse_prop(s: &str) -> Prop {
+        prop()
+            .then_ignore(end())
+            .parse(s)
+            .unwrap_or_else(|errors| panic!("could not parse '{s}': {errors:?}"))
+    }
+
+    fn random_prop(rng: &mut StdRng, depth: usize) -> Prop {
+        // Besides plain symbols, ones that could be mistaken for (part of) a keyword or a
+        // rule name, e.g. `¬e` for the rule `¬e`
+        const SYMBOLS: [&str; 7] = ["p", "q", "e", "i", "rulex", "lemma", "bottoms"];
+        if depth == 0 || rng.gen_bool(0.2) {
+            return match rng.gen_range(0..=SYMBOLS.len()) {
+                0 => Prop::Bottom,
+                i => Prop::symbol(SYMBOLS[i - 1]),
+            };
+        }
+
+        let lhs = random_prop(rng, depth - 1);
+        let rhs = random_prop(rng, depth - 1);
+        match rng.gen_range(0..4) {
+            0 => Prop::and(lhs, rhs),
+            1 => Prop::or(lhs, rhs),
+            2 => Prop::imply(lhs, rhs),
+            _ => Prop::negated(lhs),
+        }
+    }
+
+    #[test]
+    fn problem_sets() {
+        let exercises = parse_problem_set(
+            "# Week 1
+p & q |- q & p   # the first one
+    rules = &i &e_lhs ^e_rhs
+    steps = 5
+
+p -> q, q -> r ⊢ p -> r
+|- p | -p
+    without = lem mt
+",
+        )
+        .unwrap_or_else(|errors| panic!("{errors:?}"));
+
+        assert_eq!(exercises.len(), 3);
+        assert_eq!(exercises[0].sequent.conclusion, parse_prop("q & p"));
+        assert_eq!(
+            exercises[0].rules,
+            AllowedRules::Only(vec![RuleName::AndI, RuleName::AndELhs, RuleName::AndERhs])
+        );
+        assert_eq!(exercises[0].max_steps, Some(5));
+        assert_eq!(
+            exercises[1].sequent.premises,
+            vec![parse_prop("p -> q"), parse_prop("q -> r")]
+        );
+        assert_eq!(exercises[1].rules, AllowedRules::All);
+        assert!(exercises[2].sequent.premises.is_empty());
+        assert_eq!(
+            exercises[2].rules,
+            AllowedRules::AllExcept(vec![RuleName::LawOfExcludedMiddle, RuleName::ModusTollens])
+        );
+
+        assert!(parse_problem_set("p |- q\n    rules = &x").is_err());
+        assert!(parse_problem_set("p q").is_err());
+    }
+
+    #[test]
+    fn skeleton_exercises() {
+        let exercises = parse_problem_set(
+            "p -> q |- -q -> -p
+    skeleton = p -> q; [-q; [p; q; _|_]; -p]; -q -> -p
+",
+        )
+        .unwrap_or_else(|errors| panic!("{errors:?}"));
+        let skeleton = exercises[0].skeleton.as_ref().unwrap();
+        assert_eq!(skeleton.unjustified(), [1, 4, 5, 6, 7].map(StepIndex));
+        assert!(skeleton
+            .check_box_range(&BoxRange {
+                start: StepIndex(3),
+                end: StepIndex(5)
+            })
+            .is_ok());
+        // Only the premises of the sequent can be justified as premises
+        let mut skeleton = skeleton.clone();
+        assert!(skeleton.justify(StepIndex(7), StepType::Premise).is_err());
+        skeleton.justify(StepIndex(1), StepType::Premise).unwrap();
+        assert!(parse_problem_set("p |- p\n    skeleton = p; []").is_err());
+        // The skeleton must prove the sequent of the exercise
+        assert!(parse_problem_set("p |- p & p\n    skeleton = p; p").is_err());
+        assert!(parse_problem_set("p, q |- p & q\n    skeleton = q; p; p & q").is_err());
+
+        assert_eq!(
+            parse_command("justify 6 rule -i 3-5").unwrap(),
+            Command::Justify(
+                StepIndex(6),
+                StepType::Rule(Rule::NegI(StepIndex(3))),
+                vec![BoxRange {
+                    start: StepIndex(3),
+                    end: StepIndex(5)
+                }]
+            )
+        );
+        assert_eq!(
+            parse_command("justify 1 premise").unwrap(),
+            Command::Justify(StepIndex(1), StepType::Premise, vec![])
+        );
+        assert!(parse_command("justify 1 premise p").is_err());
+    }
+
+    #[test]
+    fn insert_and_edit_commands() {
+        assert_eq!(
+            parse_command("insert before 3 assume p; copy 1; discharge").unwrap(),
+            Command::Insert(
+                StepIndex(3),
+                vec![
+                    Command::Assume(parse_prop("p")),
+                    Command::Copy(StepIndex(1)),
+                    Command::Discharge
+                ]
+            )
+        );
+        assert!(parse_command("insert before 3 undo").is_err());
+        assert_eq!(
+            parse_command("edit 4 copy 2").unwrap(),
+            Command::Edit(StepIndex(4), Box::new(Command::Copy(StepIndex(2))))
+        );
+        assert!(parse_command("insert before 3").is_err());
+    }
+
+    #[test]
+    fn definitions_are_expanded() {
+        let mut proof = Proof::new();
+        let Ok(Command::Define(name, prop)) = parse_command("define A := (p -> q) & (q -> r)")
+        else {
+            panic!("define A := (p -> q) & (q -> r)");
+        };
+        proof.define(name, prop).unwrap();
+
+        let definitions = proof.definitions();
+        assert_eq!(
+            parse_command_with("premise A -> p", definitions).unwrap(),
+            Command::Premise(parse_prop("((p -> q) & (q -> r)) -> p"))
+        );
+        assert_eq!(
+            parse_command_with("insert before 1 rule lem A", definitions).unwrap(),
+            Command::Insert(
+                StepIndex(1),
+                vec![Command::Rule(
+                    Rule::LawOfExcludedMiddle(parse_prop("(p -> q) & (q -> r)")),
+                    vec![]
+                )]
+            )
+        );
+        assert_eq!(
+            parse_command_with("define B := A | r", definitions).unwrap(),
+            Command::Define("B".to_string(), parse_prop("(p -> q) & (q -> r) | r"))
+        );
+        assert!(parse_command("define A -> p := q").is_err());
+        assert!(parse_command("define A").is_err());
+    }
+
+    #[test]
+    fn error_spans() {
+        let line = "insert before 3 copy 1; rule and_i 1 2";
+        let error = Error::InvalidStepIndex {
+            index: StepIndex(1),
+        };
+        // The index of the second command, not the one of the copy
+        assert_eq!(error_span(line, 1, &error), Some(35..36));
+        assert_eq!(error_span(line, 0, &error), Some(21..22));
+
+        let error = Error::InvalidArgument {
+            position: 1,
+            expected: Expected::Shape("a conjunction".to_string()),
+            got: Prop::Bottom,
+        };
+        assert_eq!(error_span(line, 1, &error), Some(37..38));
+        assert_eq!(error_span(line, 0, &error), None);
+        assert_eq!(error_span("rule and_i 1 2", 0, &error), Some(13..14));
+    }
+
+    #[test]
+    fn note_commands() {
+        assert_eq!(
+            parse_command("note 3 \"by contradiction, see 2\"").unwrap(),
+            Command::Note(Some(StepIndex(3)), "by contradiction, see 2".to_string())
+        );
+        assert_eq!(
+            parse_command("note \"\"").unwrap(),
+            Command::Note(None, String::new())
+        );
+        assert!(parse_command("note 3").is_err());
+        assert!(parse_command("note 3 \"unfinished").is_err());
+        assert_eq!(
+            highlight("note 1 \"p -> q"),
+            vec![
+                (0..4, Highlight::Keyword),
+                (5..6, Highlight::Index),
+                (7..14, Highlight::Text)
+            ]
+        );
+    }
+
+    #[test]
+    fn box_ranges() {
+        let range = BoxRange {
+            start: StepIndex(3),
+            end: StepIndex(7),
+        };
+
+        assert_eq!(
+            parse_command("rule ->i 3-7").unwrap(),
+            Command::Rule(Rule::ImplyI(StepIndex(3)), vec![range])
+        );
+        assert_eq!(
+            parse_command("rule ->i 3").unwrap(),
+            Command::Rule(Rule::ImplyI(StepIndex(3)), vec![])
+        );
+        assert!(parse_command("rule ->e 3-7 2").is_err());
+    }
+
+    #[test]
+    fn every_rule_alias_parses() {
+        for info in RULES {
+            for alias in info.aliases {
+                assert_eq!(rule_name().then_ignore(end()).parse(*alias), Ok(info.name));
+            }
+        }
+
+        assert_eq!(
+            parse_command("rule --e 4").unwrap(),
+            Command::Rule(Rule::DoubleNegE(StepIndex(4)), vec![])
+        );
+        assert!(parse_command("rule lem 4").is_err());
+    }
+
+    #[test]
+    fn rule_examples_are_valid() {
+        for info in RULES {
+            let mut proof = Proof::new();
+            for line in info.example.lines() {
+                match parse_command(line).unwrap() {
+                    Command::Premise(prop) => {
+                        proof.add_premise(prop);
+                    }
+                    Command::Assume(prop) => {
+                        proof.add_assumption(prop);
+                    }
+                    Command::Discharge => proof.close_scope().unwrap(),
+                    Command::Rule(rule, ranges) => {
+                        for range in &ranges {
+                            proof.check_box_range(range).unwrap();
+                        }
+                        proof.apply_rule(&rule).unwrap();
+                        // In ASCII the rule is printed the way it is written
+                        let printed = format!("rule {}", rule.display(Notation::Ascii));
+                        assert!(
+                            matches!(parse_command(&printed), Ok(Command::Rule(parsed, _)) if parsed == rule),
+                            "{printed}"
+                        );
+                    }
+                    command => panic!("unexpected command {command:?} in example"),
+                }
+            }
+            let last = info.example.lines().last().unwrap();
+            assert!(
+                matches!(parse_command(last), Ok(Command::Rule(rule, _)) if RuleName::from(&rule) == info.name),
+                "{last}"
+            );
+            assert!(proof.validate().is_ok(), "{}", info.canonical_name());
+        }
+    }
+
+    #[test]
+    fn highlighting() {
+        use Highlight::*;
+        assert_eq!(
+            highlight("rule ->e 1 (p->q"),
+            vec![
+                (0..4, Keyword),
+                (5..8, RuleName),
+                (9..10, Index),
+                (11..12, Operator),
+                (12..13, Symbol),
+                (13..15, Operator),
+                (15..16, Symbol)
+            ]
+        );
+        assert_eq!(
+            highlight("premise p $ q"),
+            vec![(0..7, Keyword), (8..9, Symbol), (10..13, Invalid)]
+        );
+        assert_eq!(
+            highlight("premise ¬p v bottom"),
+            vec![
+                (0..7, Keyword),
+                (8..9, Operator),
+                (9..10, Symbol),
+                (11..12, Operator),
+                (13..19, Bottom)
+            ]
+        );
+        assert_eq!(highlight("copy 1 2")[2], (7..8, Invalid));
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        let cases = [
+            "p ∧ q ∧ r",
+            "p ∧ (q ∧ r)",
+            "p ∨ q ∧ r",
+            "(p ∨ q) ∧ r",
+            "p → q → r",
+            "(p → q) → r",
+            "¬¬p ∨ ¬(q → ⊥ ∧ r)",
+        ];
+
+        for case in cases {
+            assert_eq!(parse_prop(case).to_string(), case);
+        }
+    }
+
+    #[test]
+    fn or_and_turnstile() {
+        let p_or_not_q = Prop::or(Prop::symbol("p"), Prop::negated(Prop::symbol("q")));
+        assert_eq!(parse_prop("p|-q"), p_or_not_q);
+        assert_eq!(
+            parse_problem_set("p|-q\n").unwrap()[0].sequent,
+            Sequent {
+                premises: vec![Prop::symbol("p")],
+                conclusion: Prop::symbol("q"),
+            }
+        );
+        assert_eq!(
+            parse_problem_set("(p|-q) |- p | -q\n").unwrap()[0].sequent,
+            Sequent {
+                premises: vec![p_or_not_q.clone()],
+                conclusion: p_or_not_q,
+            }
+        );
+    }
+
+    #[test]
+    fn printed_props_parse_to_the_same_prop() {
+        let mut rng = StdRng::seed_from_u64(0xf17c4);
+        let parser = prop().then_ignore(end());
+
+        let not_e = Prop::negated(Prop::symbol("e"));
+        assert_eq!(not_e.display(Notation::Ascii).to_string(), "- e");
+        assert_eq!(
+            parse_command("premise - e").unwrap(),
+            Command::Premise(not_e)
+        );
+
+        for _ in 0..1000 {
+            let prop = random_prop(&mut rng, 6);
+            for notation in [Notation::Unicode, Notation::Ascii] {
+                let printed = prop.display(notation).to_string();
+                assert_eq!(
+                    parser.parse(printed.as_str()),
+                    Ok(prop.clone()),
+                    "{printed}"
+                );
+                assert_eq!(
+                    parse_command(&format!("premise {printed}")).unwrap(),
+                    Command::Premise(prop.clone()),
+                    "{printed}"
+                );
+            }
+        }
+    }
+}